@@ -3,15 +3,15 @@ use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 use chrono::{DateTime, Local, Utc};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use blake3::Hasher;
 
 // File categories for organization
@@ -24,9 +24,155 @@ enum FileCategory {
     Archive,
     Code,
     Executable,
+    Font,
+    DiskImage,
+    Model3D,
+    Subtitle,
+    Database,
+    Text,
+    Project,
     Other(String),
 }
 
+impl FileCategory {
+    // Map a category database name to a category
+    fn from_name(name: &str) -> FileCategory {
+        match name {
+            "Document" => FileCategory::Document,
+            "Image" => FileCategory::Image,
+            "Video" => FileCategory::Video,
+            "Audio" => FileCategory::Audio,
+            "Archive" => FileCategory::Archive,
+            "Code" => FileCategory::Code,
+            "Executable" => FileCategory::Executable,
+            "Font" => FileCategory::Font,
+            "Disk Image" => FileCategory::DiskImage,
+            "3D Model" => FileCategory::Model3D,
+            "Subtitle" => FileCategory::Subtitle,
+            "Database" => FileCategory::Database,
+            "Text" => FileCategory::Text,
+            "Project" => FileCategory::Project,
+            other => FileCategory::Other(other.to_string()),
+        }
+    }
+    
+    // Name of this category in the category database
+    fn name(&self) -> &str {
+        match self {
+            FileCategory::Document => "Document",
+            FileCategory::Image => "Image",
+            FileCategory::Video => "Video",
+            FileCategory::Audio => "Audio",
+            FileCategory::Archive => "Archive",
+            FileCategory::Code => "Code",
+            FileCategory::Executable => "Executable",
+            FileCategory::Font => "Font",
+            FileCategory::DiskImage => "Disk Image",
+            FileCategory::Model3D => "3D Model",
+            FileCategory::Subtitle => "Subtitle",
+            FileCategory::Database => "Database",
+            FileCategory::Text => "Text",
+            FileCategory::Project => "Project",
+            FileCategory::Other(name) => name,
+        }
+    }
+}
+
+// A category or subcategory in the category database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CategoryDefinition {
+    name: String,
+    folder: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    subcategories: Vec<CategoryDefinition>,
+}
+
+// Category database, embedded at build time and overridable from the config directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CategoryDatabase {
+    categories: Vec<CategoryDefinition>,
+}
+
+// Lookup tables built from one or more category databases
+#[derive(Debug, Default)]
+struct CategoryIndex {
+    by_extension: HashMap<String, (FileCategory, Option<String>)>,
+    // Keyed by "Category" or "Category/Subcategory"
+    folders: HashMap<String, String>,
+}
+
+// What decided a file's category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CategorySource {
+    #[default]
+    Extension,
+    Content,
+}
+
+// How content sniffing (magic bytes) is used when categorizing files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SniffMode {
+    // Extension only
+    Off,
+    // Sniff only when the extension is missing or unrecognized
+    #[default]
+    Fallback,
+    // Prefer the sniffed type over the extension
+    Primary,
+}
+
+// Character encoding detected for a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum TextEncoding {
+    Ascii,
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    // Some 8-bit legacy encoding such as Latin-1 or Windows-1252
+    Legacy8Bit,
+}
+
+// Line-ending style of a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+    Mixed,
+    None,
+}
+
+// Whether a file's content is text or opaque binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum ContentKind {
+    Text { encoding: TextEncoding, line_ending: LineEnding },
+    Binary,
+}
+
+// Outcome of categorizing a single file
+#[derive(Debug, Clone)]
+struct Classification {
+    category: FileCategory,
+    subcategory: Option<String>,
+    source: CategorySource,
+    content: Option<ContentKind>,
+}
+
+// A file type detected from its content
+#[derive(Debug, Clone)]
+struct SniffedType {
+    category: FileCategory,
+    extension: &'static str,
+}
+
 // File information structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct FileInfo {
@@ -34,7 +180,556 @@ struct FileInfo {
     size: u64,
     last_modified: u64,
     category: FileCategory,
+    #[serde(default)]
+    subcategory: Option<String>,
+    #[serde(default)]
+    category_source: CategorySource,
+    #[serde(default)]
+    content: Option<ContentKind>, // Text/binary detection for otherwise unknown files
     hash: Option<String>, // For duplicate detection
+    perceptual_hash: Option<u64>, // For near-duplicate image detection
+    dimensions: Option<(u32, u32)>,
+    #[serde(default)]
+    exif: Option<ExifData>,
+    #[serde(default)]
+    audio: Option<AudioTags>,
+    #[serde(default)]
+    video: Option<VideoInfo>,
+    #[serde(default)]
+    document: Option<DocumentInfo>,
+}
+
+// A file found under the same content in both trees but at different relative paths
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MovedFile {
+    a_path: PathBuf,
+    b_path: PathBuf,
+}
+
+// Result of comparing two directory trees
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TreeDiff {
+    only_in_a: Vec<PathBuf>,
+    only_in_b: Vec<PathBuf>,
+    moved: Vec<MovedFile>,
+    modified: Vec<PathBuf>,
+    identical: usize,
+    a_errors: usize,
+    b_errors: usize,
+    a_contained_in_b: bool,
+}
+
+// A single file within a duplicate report group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DuplicateReportFile {
+    path: PathBuf,
+    last_modified: u64,
+}
+
+// A duplicate group as written to report files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DuplicateReportGroup {
+    hash: String,
+    size: u64,
+    wasted_bytes: u64,
+    files: Vec<DuplicateReportFile>,
+}
+
+// A file whose content doesn't match its extension
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExtensionMismatch {
+    path: PathBuf,
+    current_extension: Option<String>,
+    detected_extension: String,
+}
+
+// A file as emitted by structured scan output
+#[derive(Debug, Clone, Serialize)]
+struct FileRecord {
+    path: PathBuf,
+    size: u64,
+    last_modified: u64,
+    category: String,
+    subcategory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exif: Option<ExifData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audio: Option<AudioTags>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    video: Option<VideoInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    document: Option<DocumentInfo>,
+}
+
+// A duplicate left out by --paranoid, and why
+#[derive(Debug, Clone, Serialize)]
+struct SkippedDuplicate {
+    path: PathBuf,
+    reason: String,
+}
+
+// Size and file count of one report category
+#[derive(Debug, Clone, Serialize)]
+struct CategoryTotal {
+    category: String,
+    size: u64,
+    files: usize,
+    percent: f64,
+}
+
+// Files last modified within an age range
+#[derive(Debug, Clone, Serialize)]
+struct AgeBucket {
+    label: String,
+    // Upper bound of the range in days; None for the oldest bucket
+    max_age_days: Option<u64>,
+    size: u64,
+    files: usize,
+}
+
+// Age buckets for the files of one report category
+#[derive(Debug, Clone, Serialize)]
+struct CategoryAges {
+    category: String,
+    buckets: Vec<AgeBucket>,
+}
+
+// Total size of a directory and everything below it
+#[derive(Debug, Clone, Serialize)]
+struct DirectoryTotal {
+    path: PathBuf,
+    size: u64,
+    files: usize,
+}
+
+// Largest files of one report category
+#[derive(Debug, Clone, Serialize)]
+struct CategoryTopFiles {
+    category: String,
+    files: Vec<FileRecord>,
+}
+
+// Settings for building the storage report
+#[derive(Debug, Clone, Copy)]
+struct ReportOptions {
+    by_subcategory: bool,
+    // Files untouched for longer than this are listed as stale
+    stale_years: u64,
+    // Length of the largest-files, largest-directories and per-category lists
+    top: usize,
+    // Length of the stale list, which --top also sets
+    stale_top: usize,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        ReportOptions {
+            by_subcategory: false,
+            stale_years: 2,
+            top: 5,
+            stale_top: 10,
+        }
+    }
+}
+
+// Aggregates behind the storage report, shared by the text and structured outputs
+#[derive(Debug, Clone, Serialize)]
+struct StorageReport {
+    total_files: usize,
+    total_size: u64,
+    categories: Vec<CategoryTotal>,
+    largest_files: Vec<FileRecord>,
+    largest_directories: Vec<DirectoryTotal>,
+    category_top_files: Vec<CategoryTopFiles>,
+    duplicate_groups: Vec<DuplicateReportGroup>,
+    age_buckets: Vec<AgeBucket>,
+    category_ages: Vec<CategoryAges>,
+    stale_years: u64,
+    stale_files: Vec<FileRecord>,
+    // Filled in by the caller after byte-verifying duplicates
+    skipped_duplicates: Vec<SkippedDuplicate>,
+}
+
+// Size totals for a directory, including everything below it
+#[derive(Debug, Clone, Default, Serialize)]
+struct DirectoryNode {
+    size: u64,
+    files: usize,
+    children: BTreeMap<String, DirectoryNode>,
+}
+
+// Top-level document of `scan --format json`
+#[derive(Debug, Serialize)]
+struct ScanOutput<'a> {
+    schema_version: u32,
+    root: &'a Path,
+    files: Vec<FileRecord>,
+    report: &'a StorageReport,
+}
+
+// One line of `scan --format ndjson`, tagged by record type
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ScanRecord<'a> {
+    Summary {
+        schema_version: u32,
+        root: &'a Path,
+        total_files: usize,
+        total_size: u64,
+    },
+    File(&'a FileRecord),
+    Category(&'a CategoryTotal),
+    LargestFile(&'a FileRecord),
+    LargestDirectory(&'a DirectoryTotal),
+    CategoryTopFile {
+        // Report category the list belongs to
+        list: &'a str,
+        #[serde(flatten)]
+        file: &'a FileRecord,
+    },
+    DuplicateGroup(&'a DuplicateReportGroup),
+    AgeBucket {
+        // Report category, or None for the overall buckets
+        category: Option<&'a str>,
+        #[serde(flatten)]
+        bucket: &'a AgeBucket,
+    },
+    StaleFile(&'a FileRecord),
+    SkippedDuplicate(&'a SkippedDuplicate),
+}
+
+// Details of a scan run exported alongside its results
+#[derive(Debug, Clone, Default)]
+struct ScanRunStats {
+    duration_secs: f64,
+    errors: usize,
+    duplicates_checked: bool,
+}
+
+// A policy for `tidyfs check`: optional filters and one or more limits on the matching files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckRule {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    paths: Vec<String>, // Globs or directories, relative to the checked directory
+    #[serde(default)]
+    max_total_size: Option<String>,
+    #[serde(default)]
+    max_files: Option<usize>,
+    #[serde(default)]
+    max_file_size: Option<String>,
+    #[serde(default)]
+    max_age: Option<String>,
+    #[serde(default)]
+    max_duplicate_waste: Option<String>,
+}
+
+// Outcome of one limit of a check rule
+#[derive(Debug, Clone, Serialize)]
+struct CheckResult {
+    rule: String,
+    limit: String,
+    passed: bool,
+    message: String,
+    files: Vec<FileRecord>, // Offenders, or the largest contributors to a total, largest first
+}
+
+// Version of the structured scan output schema, bumped on incompatible changes
+const SCAN_SCHEMA_VERSION: u32 = 2;
+
+// Version of the snapshot file layout, independent of the scan output schema
+const SNAPSHOT_SCHEMA_VERSION: u32 = 2;
+
+// Age ranges used by the report, as (label, upper bound in days)
+const AGE_BUCKETS: &[(&str, Option<u64>)] = &[
+    ("< 1 week", Some(7)),
+    ("< 1 month", Some(30)),
+    ("< 6 months", Some(182)),
+    ("< 1 year", Some(365)),
+    ("< 3 years", Some(3 * 365)),
+    ("older", None),
+];
+
+// Page template for scan --html
+const HTML_REPORT_TEMPLATE: &str = include_str!("report.html");
+
+// A single rename recorded in a journal so it can be undone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+// Journal of file operations performed by one run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Journal {
+    operation: String,
+    created: u64,
+    entries: Vec<JournalEntry>,
+}
+
+// A file as stored in a snapshot, relative to the snapshot root
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct SnapshotFile {
+    path: PathBuf,
+    size: u64,
+    // Version 1 snapshots call this "modified"
+    #[serde(alias = "modified")]
+    last_modified: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    category: String,
+}
+
+// A saved scan result, compared against later scans of the same tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    schema_version: u32,
+    root: PathBuf,
+    created: u64,
+    files: Vec<SnapshotFile>,
+}
+
+// A file whose size changed between two snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotChange {
+    path: PathBuf,
+    before: u64,
+    after: u64,
+}
+
+// A file found at a different path in the newer snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotMove {
+    from: PathBuf,
+    to: PathBuf,
+    size: u64,
+}
+
+// Total size of a category or directory in both snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SizeDelta {
+    name: String,
+    before: u64,
+    after: u64,
+}
+
+// What changed between two snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotComparison {
+    a_root: PathBuf,
+    a_created: u64,
+    b_root: PathBuf,
+    b_created: u64,
+    total_before: u64,
+    total_after: u64,
+    added: Vec<SnapshotFile>,
+    removed: Vec<SnapshotFile>,
+    grown: Vec<SnapshotChange>,
+    moved: Vec<SnapshotMove>,
+    categories: Vec<SizeDelta>,
+    directories: Vec<SizeDelta>,
+}
+
+// Views offered by the interactive browser
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UiView {
+    Tree,
+    Categories,
+    Duplicates,
+}
+
+// What a row of the interactive browser stands for
+#[derive(Debug, Clone, PartialEq)]
+enum UiEntryKind {
+    Directory(PathBuf), // Relative to the root
+    File(usize),        // Index into the scanned files
+    Category(String),
+    DuplicateGroup(usize),
+}
+
+// A row of the interactive browser
+#[derive(Debug, Clone)]
+struct UiEntry {
+    name: String,
+    size: u64,
+    files: usize,
+    kind: UiEntryKind,
+}
+
+// Text the browser is asking for before an action
+#[derive(Debug, Clone, PartialEq)]
+enum UiPrompt {
+    MoveTarget,
+    OrganizeTarget,
+    OrganizeMethod(PathBuf),
+}
+
+// An action on the marked files, run once confirmed
+#[derive(Debug, Clone, PartialEq)]
+enum UiAction {
+    Trash,
+    Move(PathBuf),
+    Organize(PathBuf, String),
+}
+
+// Input state of the interactive browser
+#[derive(Debug, Clone, PartialEq)]
+enum UiMode {
+    Browse,
+    Prompt(UiPrompt, String),
+    Confirm(UiAction),
+}
+
+// Result of handling a key press
+#[derive(Debug, Clone, PartialEq)]
+enum UiStep {
+    Continue,
+    Quit,
+    Execute(UiAction),
+}
+
+// State of the interactive browser
+struct UiApp {
+    root: PathBuf,
+    files: Vec<FileInfo>,
+    tree: DirectoryNode,
+    duplicate_groups: Vec<Vec<usize>>,
+    hashed: bool,
+    view: UiView,
+    directory: PathBuf, // Directory open in the tree view, relative to the root
+    category: Option<String>,
+    group: Option<usize>,
+    entries: Vec<UiEntry>,
+    table: ratatui::widgets::TableState,
+    marked: HashSet<usize>,
+    mode: UiMode,
+    message: Option<String>,
+}
+
+// A user-defined category with an explicit priority and optional path scope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CategoryRule {
+    name: String,
+    extensions: Vec<String>,
+    // Higher wins; built-in categories sit at 0, below custom rules of equal priority
+    #[serde(default)]
+    priority: i32,
+    // Glob patterns (or plain directories) the rule is limited to; empty means everywhere
+    #[serde(default)]
+    paths: PathScope,
+}
+
+// Globs or plain directories, stored as written and compiled once when the config is read
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<String>", into = "Vec<String>")]
+struct PathScope {
+    patterns: Vec<String>,
+    compiled: Vec<ScopePattern>,
+}
+
+// A single compiled entry of a path scope
+#[derive(Debug, Clone)]
+enum ScopePattern {
+    // Plain directories match everything beneath them
+    Directory(PathBuf),
+    Glob(glob::Pattern),
+}
+
+// A definition that matched a file, as considered by `category_matches`
+#[derive(Debug, Clone)]
+enum CategoryMatch {
+    // Entry in `category_rules`, by position
+    Rule { name: String, priority: i32, position: usize },
+    // Legacy entry in `custom_categories`
+    Custom { name: String },
+    // Entry in the category database
+    Builtin { category: FileCategory, subcategory: Option<String>, extension: String },
+}
+
+// Where organize moves projects detected by a given marker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProjectRule {
+    marker: String,
+    destination: String,
+}
+
+// Photo metadata read from EXIF
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct ExifData {
+    // DateTimeOriginal as a unix timestamp
+    date_taken: Option<u64>,
+    camera_make: Option<String>,
+    camera_model: Option<String>,
+    orientation: Option<u16>,
+}
+
+// Music metadata read from ID3v2, FLAC or MP4 tags
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct AudioTags {
+    title: Option<String>,
+    artist: Option<String>,
+    album_artist: Option<String>,
+    album: Option<String>,
+    track: Option<u32>,
+    disc: Option<u32>,
+    year: Option<u32>,
+}
+
+// Video container metadata read from MP4/MOV or Matroska headers
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct VideoInfo {
+    // Container creation time as a unix timestamp
+    created: Option<u64>,
+    duration_secs: Option<f64>,
+    width: Option<u32>,
+    height: Option<u32>,
+    codec: Option<String>,
+}
+
+// Document metadata read from PDF, OOXML, ODF or EPUB files
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct DocumentInfo {
+    title: Option<String>,
+    author: Option<String>,
+    // Creation date as a unix timestamp
+    created: Option<u64>,
+    pages: Option<u32>,
+    language: Option<String>,
+}
+
+// What scan_directory collects besides size, mtime and category
+#[derive(Debug, Clone, Copy, Default)]
+struct ScanOptions {
+    recursive: bool,
+    calculate_hashes: bool,
+    calculate_perceptual_hashes: bool,
+    // Treat directories with project markers as single items
+    detect_projects: bool,
+    // Which kinds of embedded metadata, such as EXIF, to read
+    read_metadata: MetadataKinds,
+    // Hide progress output, for machine-readable formats
+    quiet: bool,
+}
+
+// Embedded metadata to read per kind of file; the default reads none
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct MetadataKinds {
+    exif: bool,
+    audio: bool,
+    video: bool,
+    document: bool,
+}
+
+impl MetadataKinds {
+    // Every kind, for --metadata and templates
+    const ALL: MetadataKinds = MetadataKinds { exif: true, audio: true, video: true, document: true };
 }
 
 // Config structure for persistent settings
@@ -42,8 +737,31 @@ struct FileInfo {
 struct TidyConfig {
     ignore_patterns: Vec<String>,
     custom_categories: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    category_rules: Vec<CategoryRule>,
     recent_directories: Vec<PathBuf>,
     default_organization: String,
+    // Files at least this large are hashed via mmap with multithreaded blake3
+    #[serde(default = "default_mmap_hash_threshold")]
+    mmap_hash_threshold: u64,
+    #[serde(default)]
+    sniff_mode: SniffMode,
+    // Files or globs whose presence marks a directory as a project root
+    #[serde(default = "default_project_markers")]
+    project_markers: Vec<String>,
+    #[serde(default)]
+    project_rules: Vec<ProjectRule>,
+}
+
+fn default_project_markers() -> Vec<String> {
+    ["Cargo.toml", "package.json", "pyproject.toml", "go.mod", "*.sln", ".git"]
+        .iter()
+        .map(|m| m.to_string())
+        .collect()
+}
+
+fn default_mmap_hash_threshold() -> u64 {
+    128 * 1024 * 1024
 }
 
 impl Default for TidyConfig {
@@ -51,452 +769,5098 @@ impl Default for TidyConfig {
         TidyConfig {
             ignore_patterns: vec![".git".to_string(), "node_modules".to_string()],
             custom_categories: HashMap::new(),
+            category_rules: Vec::new(),
             recent_directories: Vec::new(),
             default_organization: "type".to_string(),
+            mmap_hash_threshold: default_mmap_hash_threshold(),
+            sniff_mode: SniffMode::default(),
+            project_markers: default_project_markers(),
+            project_rules: Vec::new(),
         }
     }
 }
 
-// Function to determine file category based on extension
-fn determine_category(path: &Path, config: &TidyConfig) -> FileCategory {
-    if let Some(extension) = path.extension() {
-        let ext = extension.to_string_lossy().to_lowercase();
-        
-        // Check custom categories first
-        for (category, extensions) in &config.custom_categories {
-            if extensions.iter().any(|e| e.to_lowercase() == ext) {
-                return FileCategory::Other(category.clone());
+// Multi-part extensions that must be treated as a single suffix
+const COMPOUND_EXTENSIONS: &[&str] = &[
+    "tar.gz", "tar.bz2", "tar.xz", "tar.zst", "tar.lz", "tar.lz4", "tar.lzma", "tar.z",
+    "pkg.tar.zst", "pkg.tar.xz", "user.js", "user.css", "min.js", "min.css", "d.ts",
+    "js.map", "css.map",
+];
+
+// Split a file name into stem and extension, keeping compound extensions whole.
+// Both parts keep the original case.
+fn split_extension(path: &Path) -> (String, Option<String>) {
+    let file_name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return (String::new(), None),
+    };
+    let bytes = file_name.as_bytes();
+    
+    // Compare bytes of the original name, since lowercasing can change its length.
+    // Longest match first so "pkg.tar.zst" wins over "tar.zst"
+    let compound = COMPOUND_EXTENSIONS
+        .iter()
+        .filter(|ext| {
+            let suffix_start = bytes.len().saturating_sub(ext.len());
+            bytes.len() > ext.len() + 1
+                && bytes[suffix_start - 1] == b'.'
+                && bytes[suffix_start..].eq_ignore_ascii_case(ext.as_bytes())
+        })
+        .max_by_key(|ext| ext.len());
+    
+    if let Some(ext) = compound {
+        let split_at = file_name.len() - ext.len();
+        return (
+            file_name[..split_at - 1].to_string(),
+            Some(file_name[split_at..].to_string()),
+        );
+    }
+    
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => (stem.to_string_lossy().to_string(), Some(ext.to_string_lossy().to_string())),
+        _ => (file_name, None),
+    }
+}
+
+// Lowercase extension of a file, including compound extensions like "tar.gz"
+fn file_extension(path: &Path) -> Option<String> {
+    split_extension(path).1.map(|ext| ext.to_lowercase())
+}
+
+// Built-in category database
+const BUILTIN_CATEGORIES: &str = include_str!("categories.json");
+
+impl CategoryIndex {
+    // Add a database to the index; later definitions override earlier ones
+    fn add(&mut self, database: &CategoryDatabase) {
+        for definition in &database.categories {
+            let category = FileCategory::from_name(&definition.name);
+            
+            self.folders.insert(definition.name.clone(), definition.folder.clone());
+            for ext in &definition.extensions {
+                self.by_extension.insert(ext.to_lowercase(), (category.clone(), None));
+            }
+            
+            for sub in &definition.subcategories {
+                self.folders
+                    .insert(format!("{}/{}", definition.name, sub.name), sub.folder.clone());
+                for ext in &sub.extensions {
+                    self.by_extension
+                        .insert(ext.to_lowercase(), (category.clone(), Some(sub.name.clone())));
+                }
             }
         }
+    }
+    
+    // Category and subcategory for a lowercase extension
+    fn lookup(&self, ext: &str) -> Option<(FileCategory, Option<String>)> {
+        self.by_extension.get(ext).cloned()
+    }
+    
+    // Whether a name is a category or one of its report labels ("Documents", "Documents/Spreadsheets"),
+    // ignoring ASCII case
+    fn knows_name(&self, name: &str) -> bool {
+        self.folders.iter().any(|(key, folder)| match key.split_once('/') {
+            // Subcategory labels nest the subfolder under the category's folder
+            Some((category, _)) => self
+                .folders
+                .get(category)
+                .is_some_and(|top| format!("{}/{}", top, folder).eq_ignore_ascii_case(name)),
+            None => key.eq_ignore_ascii_case(name) || folder.eq_ignore_ascii_case(name),
+        })
+    }
+    
+    // Folder name for a category, optionally nested under its subcategory
+    fn folder(&self, category: &FileCategory, subcategory: Option<&str>) -> Option<String> {
+        let top = self.folders.get(category.name())?;
         
-        // Standard categories
-        match ext.as_str() {
-            "pdf" | "doc" | "docx" | "txt" | "rtf" | "odt" | "md" | "xls" | "xlsx" | "ppt" | "pptx" => {
-                FileCategory::Document
-            }
-            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tiff" | "svg" | "webp" => FileCategory::Image,
-            "mp4" | "avi" | "mov" | "wmv" | "flv" | "mkv" | "webm" => FileCategory::Video,
-            "mp3" | "wav" | "ogg" | "flac" | "aac" | "m4a" => FileCategory::Audio,
-            "zip" | "rar" | "7z" | "tar" | "gz" | "bz2" | "xz" => FileCategory::Archive,
-            "rs" | "py" | "js" | "html" | "css" | "java" | "c" | "cpp" | "h" | "go" | "rb" | "php" | "sh" => {
-                FileCategory::Code
-            }
-            "exe" | "msi" | "app" | "dmg" | "deb" | "rpm" => FileCategory::Executable,
-            _ => FileCategory::Other(ext.to_string()),
+        match subcategory.and_then(|sub| self.folders.get(&format!("{}/{}", category.name(), sub))) {
+            Some(sub_folder) => Some(format!("{}/{}", top, sub_folder)),
+            None => Some(top.clone()),
         }
-    } else {
-        FileCategory::Other("unknown".to_string())
     }
 }
 
-// Calculate file hash for duplicate detection
-fn calculate_hash(path: &Path) -> Result<String, Box<dyn Error>> {
-    let mut file = File::open(path)?;
-    let mut hasher = Hasher::new();
-    let mut buffer = [0; 8192];
+// Load the built-in category database plus any user override in the config directory
+fn load_category_index() -> CategoryIndex {
+    let mut index = CategoryIndex::default();
     
-    loop {
-        let bytes_read = file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+    let builtin: CategoryDatabase =
+        serde_json::from_str(BUILTIN_CATEGORIES).expect("built-in category database is valid JSON");
+    index.add(&builtin);
+    
+    if let Some(config_dir) = dirs::config_dir() {
+        let override_path = config_dir.join("tidyfs").join("categories.json");
+        
+        if override_path.exists() {
+            let database = fs::read_to_string(&override_path)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| serde_json::from_str::<CategoryDatabase>(&contents).map_err(|e| e.to_string()));
+            
+            match database {
+                Ok(database) => index.add(&database),
+                Err(e) => eprintln!(
+                    "Warning: ignoring invalid category database {}: {}",
+                    override_path.display(),
+                    e
+                ),
+            }
         }
-        hasher.update(&buffer[..bytes_read]);
     }
     
-    Ok(hasher.finalize().to_hex().to_string())
+    index
 }
 
-// Get file info including size, modification time, and category
-fn get_file_info(path: &Path, config: &TidyConfig, calculate_hashes: bool) -> Result<FileInfo, Box<dyn Error>> {
-    let metadata = fs::metadata(path)?;
-    let size = metadata.len();
-    
-    let last_modified = metadata
-        .modified()?
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    
-    let category = determine_category(path, config);
+// Shared category index, loaded on first use
+fn category_index() -> &'static CategoryIndex {
+    static INDEX: OnceLock<CategoryIndex> = OnceLock::new();
+    INDEX.get_or_init(load_category_index)
+}
+
+impl CategoryMatch {
+    // Category and subcategory this match assigns
+    fn category(&self) -> (FileCategory, Option<String>) {
+        match self {
+            CategoryMatch::Rule { name, .. } | CategoryMatch::Custom { name } => {
+                (FileCategory::Other(name.clone()), None)
+            }
+            CategoryMatch::Builtin { category, subcategory, .. } => (category.clone(), subcategory.clone()),
+        }
+    }
     
-    let hash = if calculate_hashes {
-        Some(calculate_hash(path)?)
-    } else {
-        None
-    };
+    // Whether a user definition (rather than the database) matched
+    fn is_custom(&self) -> bool {
+        !matches!(self, CategoryMatch::Builtin { .. })
+    }
     
-    Ok(FileInfo {
-        path: path.to_path_buf(),
-        size,
-        last_modified,
-        category,
-        hash,
-    })
+    // Human-readable description for explain-category
+    fn describe(&self, config: &TidyConfig) -> String {
+        match self {
+            CategoryMatch::Rule { name, priority, position } => {
+                let paths = &config.category_rules[*position].paths;
+                if paths.is_empty() {
+                    format!("custom rule '{}' (priority {})", name, priority)
+                } else {
+                    format!("custom rule '{}' (priority {}, paths: {})", name, priority, paths.patterns().join(", "))
+                }
+            }
+            CategoryMatch::Custom { name } => format!("custom category '{}' (priority 0)", name),
+            CategoryMatch::Builtin { category, subcategory, extension } => match subcategory {
+                Some(sub) => format!("built-in {}/{} for .{}", category.name(), sub, extension),
+                None => format!("built-in {} for .{}", category.name(), extension),
+            },
+        }
+    }
 }
 
-// Scan directory and collect file information
-fn scan_directory(
-    dir: &Path, 
-    config: &TidyConfig, 
-    calculate_hashes: bool,
-    recursive: bool
-) -> Result<Vec<FileInfo>, Box<dyn Error>> {
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap()
-            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈"),
-    );
-    pb.set_message("Scanning directory...");
-    
-    let files_info = Arc::new(Mutex::new(Vec::new()));
-    let error_count = Arc::new(Mutex::new(0));
-    let file_count = Arc::new(Mutex::new(0));
-    
-    let walker = if recursive {
-        WalkDir::new(dir)
-    } else {
-        WalkDir::new(dir).max_depth(1)
-    };
+// Expand a leading "~/" to the home directory
+fn expand_home(pattern: &str) -> String {
+    match (pattern.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => pattern.to_string(),
+    }
+}
+
+// Make a path absolute with `.` and `..` resolved, following symlinks when the path exists
+fn canonical_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
     
-    let entries: Vec<_> = walker
-        .into_iter()
-        .filter_entry(|e| {
-            let path = e.path();
-            !config.ignore_patterns.iter().any(|pattern| {
-                path.to_string_lossy().contains(pattern)
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+impl From<Vec<String>> for PathScope {
+    fn from(patterns: Vec<String>) -> Self {
+        // Invalid globs are dropped here and simply never match
+        let compiled = patterns
+            .iter()
+            .map(|pattern| canonical_path(Path::new(&expand_home(pattern))))
+            .filter_map(|pattern| {
+                let pattern = pattern.to_string_lossy();
+                if pattern.contains(['*', '?', '[']) {
+                    glob::Pattern::new(&pattern).ok().map(ScopePattern::Glob)
+                } else {
+                    Some(ScopePattern::Directory(PathBuf::from(pattern.as_ref())))
+                }
             })
-        })
-        .filter_map(|e| e.ok())
-        .collect();
+            .collect();
+        
+        PathScope { patterns, compiled }
+    }
+}
+
+impl From<PathScope> for Vec<String> {
+    fn from(scope: PathScope) -> Self {
+        scope.patterns
+    }
+}
+
+impl PathScope {
+    // Whether the scope was left empty, i.e. applies everywhere
+    fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
     
-    pb.set_length(entries.len() as u64);
-    pb.set_message("Processing files...");
+    // Patterns as written in the config
+    fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
     
-    entries.into_par_iter().for_each(|entry| {
-        let path = entry.path();
-        if path.is_file() {
-            match get_file_info(path, config, calculate_hashes) {
-                Ok(info) => {
-                    let mut file_infos = files_info.lock().unwrap();
-                    file_infos.push(info);
-                    
-                    let mut count = file_count.lock().unwrap();
-                    *count += 1;
-                    if *count % 100 == 0 {
-                        pb.set_message(format!("Processed {} files...", *count));
-                    }
-                }
-                Err(_) => {
-                    let mut errors = error_count.lock().unwrap();
-                    *errors += 1;
-                }
-            }
+    // Check whether a path matches any of the globs or directories; an empty scope matches everything.
+    // `*` stays within one directory level, `**` matches any number of them
+    fn contains(&self, path: &Path) -> bool {
+        if self.is_empty() {
+            return true;
         }
-    });
+        
+        let canonical = canonical_path(path);
+        let options = glob::MatchOptions { require_literal_separator: true, ..glob::MatchOptions::new() };
+        
+        self.compiled.iter().any(|pattern| match pattern {
+            ScopePattern::Directory(dir) => canonical.starts_with(dir),
+            ScopePattern::Glob(glob) => glob.matches_path_with(&canonical, options),
+        })
+    }
+}
+
+// Every definition matching a file, best first. Ordered by priority, then user rules
+// before legacy custom categories before the database, then definition order.
+fn category_matches(path: &Path, config: &TidyConfig) -> Vec<CategoryMatch> {
+    let ext = match file_extension(path) {
+        Some(ext) => ext,
+        None => return Vec::new(),
+    };
     
-    let error_count = *error_count.lock().unwrap();
-    let file_count = *file_count.lock().unwrap();
+    // A compound extension falls back to its last part ("d.ts" -> "ts") when unknown
+    let last = ext.rsplit('.').next().unwrap_or(&ext).to_string();
+    let candidates = if last == ext { vec![ext.clone()] } else { vec![ext.clone(), last] };
+    let has_extension = |extensions: &[String]| {
+        extensions.iter().any(|e| candidates.contains(&e.trim_start_matches('.').to_lowercase()))
+    };
     
-    pb.finish_with_message(format!(
-        "Scan complete. Processed {} files with {} errors",
-        file_count, error_count
-    ));
+    let mut matches: Vec<(i32, u8, usize, CategoryMatch)> = Vec::new();
     
-    Ok(Arc::try_unwrap(files_info).unwrap().into_inner()?)
-}
-
-// Find duplicate files based on hash
-fn find_duplicates(files: &[FileInfo]) -> HashMap<String, Vec<&FileInfo>> {
-    let mut duplicates: HashMap<String, Vec<&FileInfo>> = HashMap::new();
+    for (position, rule) in config.category_rules.iter().enumerate() {
+        if has_extension(&rule.extensions) && rule.paths.contains(path) {
+            matches.push((
+                rule.priority,
+                0,
+                position,
+                CategoryMatch::Rule {
+                    name: rule.name.clone(),
+                    priority: rule.priority,
+                    position,
+                },
+            ));
+        }
+    }
     
-    for file in files {
-        if let Some(hash) = &file.hash {
-            duplicates.entry(hash.clone()).or_default().push(file);
+    // HashMap order is random, so legacy categories are ordered by name
+    let mut legacy: Vec<(&String, &Vec<String>)> = config.custom_categories.iter().collect();
+    legacy.sort_by_key(|(name, _)| *name);
+    
+    for (position, (name, extensions)) in legacy.into_iter().enumerate() {
+        if has_extension(extensions) {
+            matches.push((0, 1, position, CategoryMatch::Custom { name: name.clone() }));
         }
     }
     
-    // Keep only entries with more than one file (actual duplicates)
-    duplicates.retain(|_, files| files.len() > 1);
+    for (position, candidate) in candidates.iter().enumerate() {
+        if let Some((category, subcategory)) = category_index().lookup(candidate) {
+            matches.push((
+                0,
+                2,
+                position,
+                CategoryMatch::Builtin {
+                    category,
+                    subcategory,
+                    extension: candidate.clone(),
+                },
+            ));
+        }
+    }
     
-    duplicates
+    matches.sort_by_key(|(priority, group, position, _)| (std::cmp::Reverse(*priority), *group, *position));
+    matches.into_iter().map(|(_, _, _, m)| m).collect()
 }
 
-// Format size in human-readable form
-fn format_size(size: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-    
-    if size >= GB {
-        format!("{:.2} GB", size as f64 / GB as f64)
-    } else if size >= MB {
-        format!("{:.2} MB", size as f64 / MB as f64)
-    } else if size >= KB {
-        format!("{:.2} KB", size as f64 / KB as f64)
-    } else {
-        format!("{} bytes", size)
+// Category for a file that no definition recognizes, named after its extension
+fn unmatched_category(path: &Path) -> (FileCategory, Option<String>) {
+    match file_extension(path) {
+        Some(ext) => (FileCategory::Other(ext), None),
+        None => (FileCategory::Other("unknown".to_string()), None),
     }
 }
 
-// Organize files by moving them to category folders
-fn organize_files(
-    files: &[FileInfo],
-    target_dir: &Path,
-    organization_type: &str,
-    dry_run: bool,
-) -> Result<(), Box<dyn Error>> {
-    let pb = ProgressBar::new(files.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("##-"),
+// Print every definition that could categorize a file and which one wins
+fn explain_category(path: &Path, config: &TidyConfig) {
+    println!("{}", format!("Category explanation for {}", path.display()).bold().underline());
+    println!(
+        "Extension: {}",
+        file_extension(path).map(|e| format!(".{}", e)).unwrap_or_else(|| "<none>".to_string())
     );
     
-    let move_count = Arc::new(Mutex::new(0));
-    let error_count = Arc::new(Mutex::new(0));
-      for file in files {        let target_subdir = match organization_type {
-            "type" => {
-                match &file.category {
-                    FileCategory::Document => "Documents".to_string(),
-                    FileCategory::Image => "Images".to_string(),
-                    FileCategory::Video => "Videos".to_string(),
-                    FileCategory::Audio => "Audio".to_string(),
-                    FileCategory::Archive => "Archives".to_string(),
-                    FileCategory::Code => "Code".to_string(),
-                    FileCategory::Executable => "Executables".to_string(),
-                    FileCategory::Other(ext) => {
-                        if ext == "unknown" {
-                            "Other".to_string()
-                        } else {
-                            "Miscellaneous".to_string()
-                        }
-                    }
-                }
-            },
-            "date" => {
-                let datetime = DateTime::<Utc>::from_timestamp(file.last_modified as i64, 0).unwrap();
-                let local_time = datetime.with_timezone(&Local);
-                local_time.format("%Y-%m").to_string()
-            },
-            "ext" => {
-                if let Some(extension) = file.path.extension() {
-                    extension.to_string_lossy().to_string()
-                } else {
-                    "no_extension".to_string()
-                }
-            },
-            _ => "Unsorted".to_string(),
-        };
-        
-        // Create a copy for later use
-        let subdir_name = target_subdir.clone();
-        let target_path = target_dir.join(target_subdir);
-        
-        if !dry_run {
-            fs::create_dir_all(&target_path)?;
-            
-            let file_name = file.path.file_name().unwrap();
-            let destination = target_path.join(file_name);
-            
-            if destination.exists() {
-                // Handle name collision by adding a timestamp
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                
-                let file_stem = file.path.file_stem().unwrap().to_string_lossy();
-                let extension = file.path
-                    .extension()
-                    .map(|ext| format!(".{}", ext.to_string_lossy()))
-                    .unwrap_or_default();
-                
-                let new_name = format!("{}_{}{}", file_stem, now, extension);
-                let destination = target_path.join(new_name);
-                
-                match fs::rename(&file.path, &destination) {
-                    Ok(_) => {
-                        let mut count = move_count.lock().unwrap();
-                        *count += 1;
-                    }
-                    Err(_) => {
-                        let mut errors = error_count.lock().unwrap();
-                        *errors += 1;
-                    }
-                }
-            } else {
-                match fs::rename(&file.path, &destination) {
-                    Ok(_) => {
-                        let mut count = move_count.lock().unwrap();
-                        *count += 1;
-                    }
-                    Err(_) => {
-                        let mut errors = error_count.lock().unwrap();
-                        *errors += 1;
-                    }
-                }
-            }
+    let matches = category_matches(path, config);
+    
+    println!("\nMatching definitions (highest priority first):");
+    if matches.is_empty() {
+        println!("  (none)");
+    }
+    for (i, found) in matches.iter().enumerate() {
+        let marker = if i == 0 { "*".green().bold() } else { " ".normal() };
+        println!("  {} {}", marker, found.describe(config));
+    }
+    
+    match sniff_file_type(path) {
+        Some(sniffed) => println!("\nContent: looks like .{} (sniff mode: {:?})", sniffed.extension, config.sniff_mode),
+        None => println!("\nContent: not recognized (sniff mode: {:?})", config.sniff_mode),
+    }
+    
+    let classification = classify_file(path, config);
+    let label = match &classification.subcategory {
+        Some(sub) => format!("{}/{}", classification.category.name(), sub),
+        None => classification.category.name().to_string(),
+    };
+    
+    match classification.content {
+        Some(ContentKind::Text { encoding, line_ending }) => {
+            println!("Text: {:?} encoding, {:?} line endings", encoding, line_ending)
         }
-          pb.inc(1);
-        pb.set_message(format!("Moving to {}", subdir_name));
+        Some(ContentKind::Binary) => println!("Text: no, binary content"),
+        None => {}
     }
     
-    let move_count = *move_count.lock().unwrap();
-    let error_count = *error_count.lock().unwrap();
+    println!("\nResult: {} (decided by {:?})", label.bold(), classification.source);
+}
+
+// Identify a file type from its leading magic bytes
+fn sniff_magic(header: &[u8], has_extension: bool) -> Option<SniffedType> {
+    let sniffed = |category, extension| Some(SniffedType { category, extension });
+    let starts = |magic: &[u8]| header.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| header.len() >= offset + magic.len() && &header[offset..offset + magic.len()] == magic;
     
-    if dry_run {
-        pb.finish_with_message("Dry run complete. No files were moved.");
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        sniffed(FileCategory::Image, "png")
+    } else if starts(&[0xFF, 0xD8, 0xFF]) {
+        sniffed(FileCategory::Image, "jpg")
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        sniffed(FileCategory::Image, "gif")
+    } else if starts(b"BM") && is_bmp_header(header) {
+        sniffed(FileCategory::Image, "bmp")
+    } else if starts(b"II*\0") || starts(b"MM\0*") {
+        sniffed(FileCategory::Image, "tiff")
+    } else if starts(b"RIFF") && at(8, b"WEBP") {
+        sniffed(FileCategory::Image, "webp")
+    } else if starts(b"RIFF") && at(8, b"WAVE") {
+        sniffed(FileCategory::Audio, "wav")
+    } else if starts(b"RIFF") && at(8, b"AVI ") {
+        sniffed(FileCategory::Video, "avi")
+    } else if starts(b"%PDF-") {
+        sniffed(FileCategory::Document, "pdf")
+    } else if starts(b"{\\rtf") {
+        sniffed(FileCategory::Document, "rtf")
+    } else if at(4, b"ftyp") {
+        // ISO base media: the major brand tells MP4, MOV, M4A and HEIF apart
+        match header.get(8..12) {
+            Some(b"M4A ") | Some(b"M4B ") => sniffed(FileCategory::Audio, "m4a"),
+            Some(b"qt  ") => sniffed(FileCategory::Video, "mov"),
+            Some(b"heic") | Some(b"heix") | Some(b"mif1") | Some(b"msf1") => sniffed(FileCategory::Image, "heic"),
+            Some(b"avif") => sniffed(FileCategory::Image, "avif"),
+            Some(b"isom") | Some(b"iso2") | Some(b"iso4") | Some(b"iso5") | Some(b"iso6") | Some(b"mp41")
+            | Some(b"mp42") | Some(b"avc1") | Some(b"M4V ") | Some(b"M4VH") | Some(b"dash") | Some(b"f4v ")
+            | Some(b"3gp4") | Some(b"3gp5") | Some(b"3gp6") | Some(b"3g2a") => sniffed(FileCategory::Video, "mp4"),
+            // Other brands (Canon CR3, JPEG 2000, ...) reuse the box layout for unrelated formats
+            _ => None,
+        }
+    } else if starts(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        if header.windows(4).any(|w| w == b"webm") {
+            sniffed(FileCategory::Video, "webm")
+        } else {
+            sniffed(FileCategory::Video, "mkv")
+        }
+    } else if starts(b"ID3") || (!has_extension && is_mpeg_audio_frame(header)) {
+        // A bare frame sync is only a few bits, so don't let it overrule an extension
+        sniffed(FileCategory::Audio, "mp3")
+    } else if starts(b"fLaC") {
+        sniffed(FileCategory::Audio, "flac")
+    } else if starts(b"OggS") {
+        sniffed(FileCategory::Audio, "ogg")
+    } else if starts(b"PK\x03\x04") {
+        // ODF and EPUB store an uncompressed "mimetype" entry first
+        if at(30, b"mimetypeapplication/epub+zip") {
+            sniffed(FileCategory::Document, "epub")
+        } else if at(30, b"mimetypeapplication/vnd.oasis.opendocument.text") {
+            sniffed(FileCategory::Document, "odt")
+        } else if at(30, b"mimetypeapplication/vnd.oasis.opendocument.spreadsheet") {
+            sniffed(FileCategory::Document, "ods")
+        } else if at(30, b"mimetypeapplication/vnd.oasis.opendocument.presentation") {
+            sniffed(FileCategory::Document, "odp")
+        } else {
+            sniffed(FileCategory::Archive, "zip")
+        }
+    } else if starts(&[0x1F, 0x8B]) {
+        sniffed(FileCategory::Archive, "gz")
+    } else if starts(b"BZh") {
+        sniffed(FileCategory::Archive, "bz2")
+    } else if starts(&[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00]) {
+        sniffed(FileCategory::Archive, "xz")
+    } else if starts(&[0x28, 0xB5, 0x2F, 0xFD]) {
+        sniffed(FileCategory::Archive, "zst")
+    } else if starts(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]) {
+        sniffed(FileCategory::Archive, "7z")
+    } else if starts(b"Rar!\x1a\x07") {
+        sniffed(FileCategory::Archive, "rar")
+    } else if at(257, b"ustar") {
+        sniffed(FileCategory::Archive, "tar")
+    } else if starts(b"\x7fELF") {
+        sniffed(FileCategory::Executable, "elf")
+    } else if starts(b"MZ") && is_pe_header(header) {
+        sniffed(FileCategory::Executable, "exe")
+    } else if starts(&[0xFE, 0xED, 0xFA, 0xCE])
+        || starts(&[0xFE, 0xED, 0xFA, 0xCF])
+        || starts(&[0xCE, 0xFA, 0xED, 0xFE])
+        || starts(&[0xCF, 0xFA, 0xED, 0xFE])
+    {
+        sniffed(FileCategory::Executable, "macho")
+    } else if starts(b"#!") {
+        sniffed(FileCategory::Code, "sh")
     } else {
-        pb.finish_with_message(format!(
-            "Organization complete. Moved {} files with {} errors",
-            move_count, error_count
-        ));
+        None
     }
+}
+
+// Check the BMP file header reserved fields and DIB header size behind "BM"
+fn is_bmp_header(header: &[u8]) -> bool {
+    let Some(dib_size) = header.get(14..18) else {
+        return false;
+    };
+    let dib_size = u32::from_le_bytes([dib_size[0], dib_size[1], dib_size[2], dib_size[3]]);
     
-    Ok(())
+    header[6..10] == [0, 0, 0, 0] && matches!(dib_size, 12 | 40 | 52 | 56 | 64 | 108 | 124)
 }
 
-// Load config from file or create default
-fn load_config() -> Result<TidyConfig, Box<dyn Error>> {
-    let config_dir = dirs::config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("tidyfs");
+// Follow the DOS header's e_lfanew pointer to the "PE\0\0" signature behind "MZ"
+fn is_pe_header(header: &[u8]) -> bool {
+    let Some(offset) = header.get(0x3C..0x40) else {
+        return false;
+    };
+    let offset = u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize;
     
-    fs::create_dir_all(&config_dir)?;
+    header.get(offset..offset.saturating_add(4)) == Some(b"PE\0\0")
+}
+
+// Check an MPEG audio frame header: sync bits, Layer III, and valid bitrate and sample rate
+fn is_mpeg_audio_frame(header: &[u8]) -> bool {
+    if header.len() < 4 || header[0] != 0xFF || (header[1] & 0xE6) != 0xE2 {
+        return false;
+    }
     
-    let config_path = config_dir.join("config.json");
+    let bitrate = header[2] >> 4;
+    let sample_rate = (header[2] >> 2) & 0x03;
+    (header[1] & 0x18) != 0x08 && bitrate != 0 && bitrate != 0x0F && sample_rate != 0x03
+}
+
+// Tell OOXML documents apart from plain ZIP archives by their entry names
+fn sniff_ooxml(path: &Path) -> Option<SniffedType> {
+    let archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    let names: Vec<&str> = archive.file_names().collect();
     
-    if config_path.exists() {
-        let mut file = File::open(config_path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        
-        let config: TidyConfig = serde_json::from_str(&contents)?;
-        Ok(config)
-    } else {
-        let config = TidyConfig::default();
-        save_config(&config)?;
-        Ok(config)
+    if !names.contains(&"[Content_Types].xml") {
+        return None;
     }
+    
+    let extension = if names.iter().any(|n| n.starts_with("word/")) {
+        "docx"
+    } else if names.iter().any(|n| n.starts_with("xl/")) {
+        "xlsx"
+    } else if names.iter().any(|n| n.starts_with("ppt/")) {
+        "pptx"
+    } else {
+        return None;
+    };
+    
+    Some(SniffedType {
+        category: FileCategory::Document,
+        extension,
+    })
 }
 
-// Save config to file
-fn save_config(config: &TidyConfig) -> Result<(), Box<dyn Error>> {
-    let config_dir = dirs::config_dir()
-        .ok_or("Could not determine config directory")?
-        .join("tidyfs");
+// Determine a file's type by reading its content
+fn sniff_file_type(path: &Path) -> Option<SniffedType> {
+    let mut file = File::open(path).ok()?;
+    let mut header = [0u8; 512];
+    let mut len = 0;
     
-    fs::create_dir_all(&config_dir)?;
+    while len < header.len() {
+        match file.read(&mut header[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return None,
+        }
+    }
     
-    let config_path = config_dir.join("config.json");
-    let config_json = serde_json::to_string_pretty(config)?;
+    let sniffed = sniff_magic(&header[..len], path.extension().is_some())?;
     
-    let mut file = File::create(config_path)?;
-    file.write_all(config_json.as_bytes())?;
+    if sniffed.extension == "zip" {
+        return sniff_ooxml(path).or(Some(sniffed));
+    }
     
-    Ok(())
+    Some(sniffed)
 }
 
-// Update config with a new recently used directory
-fn update_recent_directories(config: &mut TidyConfig, dir: &Path) -> Result<(), Box<dyn Error>> {
-    let dir_path = dir.to_path_buf();
+// Categorize a file by extension and, depending on the sniff mode, by content
+fn classify_file(path: &Path, config: &TidyConfig) -> Classification {
+    let matches = category_matches(path, config);
+    let is_custom = matches.first().is_some_and(|m| m.is_custom());
+    let (by_extension, subcategory) = match matches.first() {
+        Some(found) => found.category(),
+        None => unmatched_category(path),
+    };
     
-    // Remove if already exists
-    config.recent_directories.retain(|path| path != &dir_path);
+    // Custom categories are explicit user choices and always win
+    let should_sniff = !is_custom
+        && match config.sniff_mode {
+            SniffMode::Off => false,
+            SniffMode::Fallback => matches.is_empty(),
+            SniffMode::Primary => true,
+        };
+    
+    if should_sniff {
+        if let Some(sniffed) = sniff_file_type(path) {
+            // The database may know a subcategory for the detected type (e.g. xlsx)
+            let (category, subcategory) = category_index()
+                .lookup(sniffed.extension)
+                .unwrap_or((sniffed.category, None));
+            return Classification {
+                category,
+                subcategory,
+                source: CategorySource::Content,
+                content: None,
+            };
+        }
+    }
+    
+    // Nothing recognized the file, so at least tell text from binary
+    if matches.is_empty() && config.sniff_mode != SniffMode::Off {
+        if let Some(content) = detect_text(path) {
+            if let ContentKind::Text { .. } = content {
+                return Classification {
+                    category: FileCategory::Text,
+                    subcategory: None,
+                    source: CategorySource::Content,
+                    content: Some(content),
+                };
+            }
+            
+            return Classification {
+                category: by_extension,
+                subcategory,
+                source: CategorySource::Extension,
+                content: Some(content),
+            };
+        }
+    }
+    
+    Classification {
+        category: by_extension,
+        subcategory,
+        source: CategorySource::Extension,
+        content: None,
+    }
+}
+
+// Classify a file prefix as text (with encoding and line endings) or binary
+fn classify_text(prefix: &[u8]) -> ContentKind {
+    let (encoding, body) = if let Some(rest) = prefix.strip_prefix(b"\xEF\xBB\xBF") {
+        (Some(TextEncoding::Utf8Bom), rest)
+    } else if let Some(rest) = prefix.strip_prefix(b"\xFF\xFE") {
+        (Some(TextEncoding::Utf16Le), rest)
+    } else if let Some(rest) = prefix.strip_prefix(b"\xFE\xFF") {
+        (Some(TextEncoding::Utf16Be), rest)
+    } else {
+        (None, prefix)
+    };
+    
+    // UTF-16 is judged on its code units; everything else on bytes
+    let units: Vec<u16> = match encoding {
+        Some(TextEncoding::Utf16Le) => body.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect(),
+        Some(TextEncoding::Utf16Be) => body.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect(),
+        _ => body.iter().map(|&b| b as u16).collect(),
+    };
+    
+    // NUL and most C0 controls don't appear in text
+    let is_control = |u: u16| u < 0x20 && !matches!(u, 0x09 | 0x0A | 0x0C | 0x0D | 0x1B);
+    let controls = units.iter().filter(|&&u| is_control(u)).count();
+    if units.contains(&0) || controls * 100 > units.len().max(1) {
+        return ContentKind::Binary;
+    }
+    
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => match std::str::from_utf8(body) {
+            Ok(text) if text.is_ascii() => TextEncoding::Ascii,
+            Ok(_) => TextEncoding::Utf8,
+            // The prefix may cut a multi-byte character in half
+            Err(e) if e.error_len().is_none() => TextEncoding::Utf8,
+            Err(_) => TextEncoding::Legacy8Bit,
+        },
+    };
+    
+    let mut crlf = 0;
+    let mut lf = 0;
+    let mut cr = 0;
+    let mut i = 0;
+    while i < units.len() {
+        match units[i] {
+            0x0D if units.get(i + 1) == Some(&0x0A) => {
+                crlf += 1;
+                i += 1;
+            }
+            0x0D => cr += 1,
+            0x0A => lf += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    
+    let line_ending = match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => LineEnding::None,
+        (true, false, false) => LineEnding::Lf,
+        (false, true, false) => LineEnding::Crlf,
+        (false, false, true) => LineEnding::Cr,
+        _ => LineEnding::Mixed,
+    };
+    
+    ContentKind::Text { encoding, line_ending }
+}
+
+// Read the start of a file and classify it as text or binary
+fn detect_text(path: &Path) -> Option<ContentKind> {
+    let mut prefix = Vec::with_capacity(8192);
+    File::open(path).ok()?.take(8192).read_to_end(&mut prefix).ok()?;
+    
+    Some(classify_text(&prefix))
+}
+
+// Extensions that are acceptable for content detected as `detected`
+fn compatible_extensions(detected: &str) -> &'static [&'static str] {
+    match detected {
+        "jpg" => &["jpg", "jpeg", "jpe", "jfif"],
+        "png" => &["png", "apng"],
+        "tiff" => &["tiff", "tif"],
+        "bmp" => &["bmp", "dib"],
+        "heic" => &["heic", "heif"],
+        // Brands inside the ISO base media family are often mixed up, so accept any sibling
+        "mp4" | "mov" | "m4a" => &["mp4", "m4v", "m4a", "m4b", "m4p", "mov", "qt", "3gp", "3g2"],
+        "mkv" => &["mkv", "mka", "mk3d", "webm"],
+        "ogg" => &["ogg", "oga", "ogv", "opus", "spx"],
+        "pdf" => &["pdf", "ai"],
+        "zip" => &["zip", "jar", "war", "apk", "aab", "xpi", "whl", "ipa", "nupkg", "vsix", "cbz", "kmz", "odg", "vsdx"],
+        "docx" => &["docx", "docm", "dotx", "dotm"],
+        "xlsx" => &["xlsx", "xlsm", "xltx", "xltm"],
+        "pptx" => &["pptx", "pptm", "potx", "ppsx"],
+        "odt" => &["odt", "ott"],
+        "ods" => &["ods", "ots"],
+        "odp" => &["odp", "otp"],
+        "gz" => &["gz", "tgz"],
+        "bz2" => &["bz2", "tbz", "tbz2"],
+        "xz" => &["xz", "txz"],
+        "zst" => &["zst", "tzst"],
+        "exe" => &["exe", "dll", "sys", "scr", "ocx", "efi", "msi"],
+        "gif" => &["gif"],
+        "webp" => &["webp"],
+        "avif" => &["avif"],
+        "wav" => &["wav"],
+        "avi" => &["avi"],
+        "rtf" => &["rtf"],
+        "webm" => &["webm"],
+        "mp3" => &["mp3"],
+        "flac" => &["flac"],
+        "7z" => &["7z"],
+        "rar" => &["rar"],
+        "tar" => &["tar"],
+        "epub" => &["epub"],
+        _ => &[],
+    }
+}
+
+// Database categories whose formats are built on a container, as (category, subcategory) names
+fn container_family(detected: &str) -> &'static [(&'static str, Option<&'static str>)] {
+    match detected {
+        // Most camera raw formats are TIFF underneath
+        "tiff" => &[("Image", Some("Raw"))],
+        // Office suites and 3D printing formats package their files in a zip
+        "zip" => &[("Document", Some("Spreadsheet")), ("Document", Some("Presentation")), ("3D Model", None)],
+        _ => &[],
+    }
+}
+
+// Whether a lowercase extension is acceptable for content detected as `detected`
+fn is_compatible_extension(detected: &str, ext: &str) -> bool {
+    if compatible_extensions(detected).contains(&ext) {
+        return true;
+    }
+    
+    // An extension with a signature of its own has to match it
+    if !compatible_extensions(ext).is_empty() {
+        return false;
+    }
+    
+    category_index().lookup(ext).is_some_and(|(category, subcategory)| {
+        container_family(detected).iter().any(|(name, sub)| {
+            category.name() == *name && (sub.is_none() || *sub == subcategory.as_deref())
+        })
+    })
+}
+
+// Check whether a file's content disagrees with its extension
+fn find_extension_mismatch(path: &Path) -> Option<ExtensionMismatch> {
+    let sniffed = sniff_file_type(path)?;
+    
+    // Types without a conventional suffix (ELF, Mach-O, scripts) can't be judged
+    if compatible_extensions(sniffed.extension).is_empty() {
+        return None;
+    }
+    
+    let current = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    
+    if current.as_deref().is_some_and(|ext| is_compatible_extension(sniffed.extension, ext)) {
+        return None;
+    }
+    
+    Some(ExtensionMismatch {
+        path: path.to_path_buf(),
+        current_extension: current,
+        detected_extension: sniffed.extension.to_string(),
+    })
+}
+
+// Find every scanned file whose content disagrees with its extension
+fn find_mismatched_extensions(files: &[FileInfo]) -> Vec<ExtensionMismatch> {
+    let mut mismatches: Vec<ExtensionMismatch> = files
+        .par_iter()
+        .filter_map(|f| find_extension_mismatch(&f.path))
+        .collect();
+    
+    mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+    mismatches
+}
+
+// Calculate file hash for duplicate detection
+fn calculate_hash(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Hasher::new();
+    let mut buffer = [0; 8192];
+    
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+// Hash a large file by memory mapping it and splitting the work across the rayon pool
+fn calculate_hash_mmap(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut hasher = Hasher::new();
+    hasher.update_mmap_rayon(path)?;
+    
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+// Calculate a difference hash (dHash) on decoded pixels, returning it with the image resolution
+fn calculate_perceptual_hash(path: &Path) -> Result<(u64, (u32, u32)), Box<dyn Error>> {
+    let image = image::open(path)?;
+    let dimensions = (image.width(), image.height());
+    
+    // Shrink to 9x8 grayscale so each row yields 8 left/right comparisons
+    let small = image
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .into_luma8();
+    
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    
+    Ok((hash, dimensions))
+}
+
+// Read a u16 from TIFF data in the given byte order
+fn tiff_u16(data: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let bytes: [u8; 2] = data.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+}
+
+// Read a u32 from TIFF data in the given byte order
+fn tiff_u32(data: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let bytes: [u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+}
+
+// Read the tags of one IFD as (tag, type, count, offset of the value field)
+fn tiff_ifd_entries(tiff: &[u8], ifd_offset: usize, little_endian: bool) -> Vec<(u16, u16, u32, usize)> {
+    let count = match tiff_u16(tiff, ifd_offset, little_endian) {
+        Some(count) => count as usize,
+        None => return Vec::new(),
+    };
+    
+    (0..count)
+        .filter_map(|i| {
+            let entry = ifd_offset + 2 + i * 12;
+            Some((
+                tiff_u16(tiff, entry, little_endian)?,
+                tiff_u16(tiff, entry + 2, little_endian)?,
+                tiff_u32(tiff, entry + 4, little_endian)?,
+                entry + 8,
+            ))
+        })
+        .collect()
+}
+
+// Read an ASCII tag value, stored inline when it fits in four bytes
+fn tiff_ascii(tiff: &[u8], count: u32, value_at: usize, little_endian: bool) -> Option<String> {
+    let count = count as usize;
+    let start = if count <= 4 { value_at } else { tiff_u32(tiff, value_at, little_endian)? as usize };
+    let bytes = tiff.get(start..start.checked_add(count)?)?;
+    
+    let text = String::from_utf8_lossy(bytes)
+        .trim_end_matches(['\0', ' '])
+        .trim()
+        .to_string();
+    
+    if text.is_empty() { None } else { Some(text) }
+}
+
+// Convert an EXIF "YYYY:MM:DD HH:MM:SS" date (local time unless an offset is given) to a timestamp
+fn parse_exif_date(date: &str, offset: Option<&str>) -> Option<u64> {
+    let naive = chrono::NaiveDateTime::parse_from_str(date, "%Y:%m:%d %H:%M:%S").ok()?;
+    
+    let timestamp = match offset.and_then(|o| chrono::DateTime::parse_from_str(&format!("{} {}", date, o), "%Y:%m:%d %H:%M:%S %:z").ok()) {
+        Some(with_offset) => with_offset.timestamp(),
+        None => naive.and_local_timezone(Local).earliest()?.timestamp(),
+    };
+    
+    u64::try_from(timestamp).ok()
+}
+
+// Parse EXIF fields from a TIFF structure (JPEG APP1 payload, HEIF Exif item or TIFF file)
+fn parse_tiff_exif(tiff: &[u8]) -> Option<ExifData> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    
+    if tiff_u16(tiff, 2, little_endian)? != 42 {
+        return None;
+    }
+    
+    let mut exif = ExifData::default();
+    let mut exif_ifd = None;
+    let mut date_time = None;
+    
+    for (tag, _, count, value_at) in tiff_ifd_entries(tiff, tiff_u32(tiff, 4, little_endian)? as usize, little_endian) {
+        match tag {
+            0x010F => exif.camera_make = tiff_ascii(tiff, count, value_at, little_endian),
+            0x0110 => exif.camera_model = tiff_ascii(tiff, count, value_at, little_endian),
+            0x0112 => exif.orientation = tiff_u16(tiff, value_at, little_endian),
+            0x0132 => date_time = tiff_ascii(tiff, count, value_at, little_endian),
+            0x8769 => exif_ifd = tiff_u32(tiff, value_at, little_endian),
+            _ => {}
+        }
+    }
+    
+    let mut date_original = None;
+    let mut offset_original = None;
+    
+    if let Some(exif_ifd) = exif_ifd {
+        for (tag, _, count, value_at) in tiff_ifd_entries(tiff, exif_ifd as usize, little_endian) {
+            match tag {
+                0x9003 => date_original = tiff_ascii(tiff, count, value_at, little_endian),
+                0x9011 => offset_original = tiff_ascii(tiff, count, value_at, little_endian),
+                _ => {}
+            }
+        }
+    }
+    
+    // Prefer the capture time; IFD0 DateTime is when the file was last edited
+    exif.date_taken = date_original
+        .as_deref()
+        .and_then(|d| parse_exif_date(d, offset_original.as_deref()))
+        .or_else(|| date_time.as_deref().and_then(|d| parse_exif_date(d, None)));
+    
+    Some(exif)
+}
+
+// Find the TIFF payload of the Exif APP1 segment in a JPEG
+fn jpeg_exif_payload(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        
+        let marker = data[pos + 1];
+        // Start of scan: image data follows, no more metadata
+        if marker == 0xDA {
+            return None;
+        }
+        
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + length)?;
+        
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return Some(&segment[6..]);
+        }
+        
+        pos += 2 + length;
+    }
+    
+    None
+}
+
+// Iterate the ISO base media boxes in `data` as (type, payload)
+fn iso_boxes(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+    
+    while pos + 8 <= data.len() {
+        let size = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let box_type = &data[pos + 4..pos + 8];
+        
+        let (header, size) = match size {
+            0 => (8, data.len() - pos),
+            1 => match data.get(pos + 8..pos + 16) {
+                Some(large) => (16, u64::from_be_bytes(large.try_into().unwrap()) as usize),
+                None => break,
+            },
+            size => (8, size),
+        };
+        
+        if size < header || pos + header > data.len() {
+            break;
+        }
+        
+        let end = pos.saturating_add(size).min(data.len());
+        boxes.push((box_type, &data[pos + header..end]));
+        pos = pos.saturating_add(size);
+    }
+    
+    boxes
+}
+
+// Read a big-endian unsigned integer of 0, 2, 4 or 8 bytes
+fn read_be_uint(data: &[u8], offset: usize, size: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + size)?;
+    Some(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+// Locate the Exif item in a HEIF file's meta box as (file offset, length)
+fn heif_exif_location(data: &[u8]) -> Option<(u64, u64)> {
+    let meta = iso_boxes(data).into_iter().find(|(t, _)| *t == b"meta")?.1;
+    // meta is a full box: skip version and flags
+    let children = iso_boxes(meta.get(4..)?);
+    
+    let iinf = children.iter().find(|(t, _)| *t == b"iinf")?.1;
+    let entries_at = if iinf.first()? == &0 { 6 } else { 8 };
+    let exif_id = iso_boxes(iinf.get(entries_at..)?).into_iter().find_map(|(t, infe)| {
+        if t != b"infe" {
+            return None;
+        }
+        match infe.first()? {
+            2 if infe.get(8..12)? == b"Exif" => read_be_uint(infe, 4, 2),
+            3 if infe.get(10..14)? == b"Exif" => read_be_uint(infe, 4, 4),
+            _ => None,
+        }
+    })?;
+    
+    let iloc = children.iter().find(|(t, _)| *t == b"iloc")?.1;
+    let version = *iloc.first()?;
+    let offset_size = (iloc.get(4)? >> 4) as usize;
+    let length_size = (iloc.get(4)? & 0x0F) as usize;
+    let base_offset_size = (iloc.get(5)? >> 4) as usize;
+    let index_size = if version > 0 { (iloc.get(5)? & 0x0F) as usize } else { 0 };
+    let id_size = if version < 2 { 2 } else { 4 };
+    
+    let mut pos = 6;
+    let item_count = read_be_uint(iloc, pos, id_size)?;
+    pos += id_size;
+    
+    for _ in 0..item_count {
+        let item_id = read_be_uint(iloc, pos, id_size)?;
+        pos += id_size;
+        if version > 0 {
+            pos += 2; // construction method
+        }
+        pos += 2; // data reference index
+        let base_offset = read_be_uint(iloc, pos, base_offset_size)?;
+        pos += base_offset_size;
+        let extent_count = read_be_uint(iloc, pos, 2)?;
+        pos += 2;
+        
+        let mut first_extent = None;
+        for _ in 0..extent_count {
+            pos += index_size;
+            let extent_offset = read_be_uint(iloc, pos, offset_size)?;
+            pos += offset_size;
+            let extent_length = read_be_uint(iloc, pos, length_size)?;
+            pos += length_size;
+            if first_extent.is_none() {
+                // Crafted offsets can overflow; treat them as a missing item
+                first_extent = Some((base_offset.checked_add(extent_offset)?, extent_length));
+            }
+        }
+        
+        if item_id == exif_id {
+            return first_extent;
+        }
+    }
+    
+    None
+}
+
+// Read EXIF metadata from a JPEG, TIFF-based or HEIF image
+fn read_exif(path: &Path) -> Option<ExifData> {
+    let mut file = File::open(path).ok()?;
+    let mut data = Vec::new();
+    (&mut file).take(256 * 1024).read_to_end(&mut data).ok()?;
+    
+    if data.starts_with(&[0xFF, 0xD8]) {
+        parse_tiff_exif(jpeg_exif_payload(&data)?)
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        parse_tiff_exif(&data)
+    } else if data.get(4..8) == Some(b"ftyp") {
+        let (offset, length) = heif_exif_location(&data)?;
+        
+        let mut item = vec![0u8; length.min(1024 * 1024) as usize];
+        file.seek(std::io::SeekFrom::Start(offset)).ok()?;
+        file.read_exact(&mut item).ok()?;
+        
+        // The item starts with the offset to the TIFF header
+        let tiff_offset = 4 + u32::from_be_bytes(item.get(0..4)?.try_into().ok()?) as usize;
+        parse_tiff_exif(item.get(tiff_offset..)?)
+    } else {
+        None
+    }
+}
+
+// Parse the leading number of a tag value such as "3/12" or "2004-05-01"
+fn parse_tag_number(value: &str) -> Option<u32> {
+    let digits: String = value.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok().filter(|&n| n > 0)
+}
+
+// Store a tag value under its normalized field name
+fn set_audio_tag(tags: &mut AudioTags, field: &str, value: &str) {
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    
+    match field {
+        "title" => tags.title = Some(value.to_string()),
+        "artist" => tags.artist = Some(value.to_string()),
+        "album_artist" => tags.album_artist = Some(value.to_string()),
+        "album" => tags.album = Some(value.to_string()),
+        "track" => tags.track = parse_tag_number(value),
+        "disc" => tags.disc = parse_tag_number(value),
+        "year" => tags.year = parse_tag_number(value).filter(|&y| y >= 1000),
+        _ => {}
+    }
+}
+
+// Decode the first string of an ID3v2 text frame
+fn decode_id3_text(data: &[u8]) -> Option<String> {
+    let (&encoding, text) = data.split_first()?;
+    
+    let utf16 = |bytes: &[u8], little_endian: bool| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| if little_endian { u16::from_le_bytes([c[0], c[1]]) } else { u16::from_be_bytes([c[0], c[1]]) })
+            .take_while(|&u| u != 0)
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    
+    let value = match encoding {
+        0 => text.iter().take_while(|&&b| b != 0).map(|&b| b as char).collect(),
+        1 => match text {
+            [0xFF, 0xFE, rest @ ..] => utf16(rest, true),
+            [0xFE, 0xFF, rest @ ..] => utf16(rest, false),
+            _ => utf16(text, false),
+        },
+        2 => utf16(text, false),
+        3 => String::from_utf8_lossy(text.split(|&b| b == 0).next()?).to_string(),
+        _ => return None,
+    };
+    
+    Some(value)
+}
+
+// Decode a 28-bit syncsafe integer used in ID3v2 sizes
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, &b| (acc << 7) | (b & 0x7F) as usize)
+}
+
+// Read ID3v2.2-2.4 text frames
+fn read_id3v2(file: &mut File) -> Option<AudioTags> {
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header).ok()?;
+    
+    let version = header[3];
+    let size = syncsafe(&header[6..10]);
+    let mut data = Vec::new();
+    // Cap the read so huge embedded artwork doesn't pull in the whole file
+    file.take(size.min(16 * 1024 * 1024) as u64).read_to_end(&mut data).ok()?;
+    
+    let mut pos = 0;
+    if header[5] & 0x40 != 0 {
+        // Skip the extended header
+        pos = match version {
+            3 => 4 + tiff_u32(&data, 0, false)? as usize,
+            4 => syncsafe(data.get(0..4)?),
+            _ => 0,
+        };
+    }
+    
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut tags = AudioTags::default();
+    
+    while pos + header_len <= data.len() && data[pos] != 0 {
+        let id = &data[pos..pos + id_len];
+        let frame_size = match version {
+            2 => read_be_uint(&data, pos + 3, 3)? as usize,
+            3 => read_be_uint(&data, pos + 4, 4)? as usize,
+            _ => syncsafe(&data[pos + 4..pos + 8]),
+        };
+        
+        let body = match data.get(pos + header_len..pos + header_len + frame_size) {
+            Some(body) => body,
+            None => break,
+        };
+        
+        let field = match id {
+            b"TIT2" | b"TT2" => "title",
+            b"TPE1" | b"TP1" => "artist",
+            b"TPE2" | b"TP2" => "album_artist",
+            b"TALB" | b"TAL" => "album",
+            b"TRCK" | b"TRK" => "track",
+            b"TPOS" | b"TPA" => "disc",
+            b"TYER" | b"TDRC" | b"TYE" => "year",
+            _ => "",
+        };
+        
+        if !field.is_empty() {
+            if let Some(value) = decode_id3_text(body) {
+                set_audio_tag(&mut tags, field, &value);
+            }
+        }
+        
+        pos += header_len + frame_size;
+    }
+    
+    Some(tags)
+}
+
+// Read the Vorbis comment block of a FLAC file
+fn read_flac_tags(file: &mut File) -> Option<AudioTags> {
+    file.seek(std::io::SeekFrom::Start(4)).ok()?;
+    
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header).ok()?;
+        let length = read_be_uint(&header, 1, 3)? as usize;
+        
+        if header[0] & 0x7F == 4 {
+            let mut block = Vec::new();
+            file.take(length as u64).read_to_end(&mut block).ok()?;
+            
+            let mut tags = AudioTags::default();
+            let vendor_len = u32::from_le_bytes(block.get(0..4)?.try_into().ok()?) as usize;
+            let mut pos = 4 + vendor_len;
+            let count = u32::from_le_bytes(block.get(pos..pos + 4)?.try_into().ok()?);
+            pos += 4;
+            
+            for _ in 0..count {
+                let len = u32::from_le_bytes(block.get(pos..pos + 4)?.try_into().ok()?) as usize;
+                let comment = String::from_utf8_lossy(block.get(pos + 4..pos + 4 + len)?);
+                pos += 4 + len;
+                
+                if let Some((key, value)) = comment.split_once('=') {
+                    let field = match key.to_ascii_uppercase().as_str() {
+                        "TITLE" => "title",
+                        "ARTIST" => "artist",
+                        "ALBUMARTIST" | "ALBUM ARTIST" => "album_artist",
+                        "ALBUM" => "album",
+                        "TRACKNUMBER" => "track",
+                        "DISCNUMBER" => "disc",
+                        "DATE" | "YEAR" => "year",
+                        _ => "",
+                    };
+                    set_audio_tag(&mut tags, field, value);
+                }
+            }
+            
+            return Some(tags);
+        }
+        
+        // Bit 7 flags the last metadata block
+        if header[0] & 0x80 != 0 {
+            return None;
+        }
+        file.seek(std::io::SeekFrom::Current(length as i64)).ok()?;
+    }
+}
+
+// Read a top-level ISO base media box from a file by type, skipping the others
+fn read_top_level_box(file: &mut File, wanted: &[u8; 4], limit: u64) -> Option<Vec<u8>> {
+    let file_len = file.metadata().ok()?.len();
+    let mut pos: u64 = 0;
+    
+    while pos.saturating_add(8) <= file_len {
+        file.seek(std::io::SeekFrom::Start(pos)).ok()?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8]).ok()?;
+        
+        let (header_len, size) = match read_be_uint(&header, 0, 4)? {
+            0 => (8, file_len - pos),
+            1 => {
+                file.read_exact(&mut header[8..16]).ok()?;
+                (16, read_be_uint(&header, 8, 8)?)
+            }
+            size => (8, size),
+        };
+        
+        if size < header_len {
+            return None;
+        }
+        
+        if &header[4..8] == wanted {
+            let mut data = Vec::new();
+            file.take((size - header_len).min(limit)).read_to_end(&mut data).ok()?;
+            return Some(data);
+        }
+        
+        // A crafted size can overflow the position; stop rather than wrap around
+        pos = pos.checked_add(size)?;
+    }
+    
+    None
+}
+
+// Read iTunes-style metadata atoms from an MP4/M4A file
+fn read_mp4_tags(file: &mut File) -> Option<AudioTags> {
+    let moov = read_top_level_box(file, b"moov", 64 * 1024 * 1024)?;
+    let udta = iso_boxes(&moov).into_iter().find(|(t, _)| *t == b"udta")?.1;
+    let meta = iso_boxes(udta).into_iter().find(|(t, _)| *t == b"meta")?.1;
+    // meta is usually a full box, but QuickTime writes it without version and flags
+    let meta = if meta.get(4..8) == Some(b"hdlr") { meta } else { meta.get(4..)? };
+    let ilst = iso_boxes(meta).into_iter().find(|(t, _)| *t == b"ilst")?.1;
+    
+    let mut tags = AudioTags::default();
+    for (item, payload) in iso_boxes(ilst) {
+        let value = match iso_boxes(payload).into_iter().find(|(t, _)| *t == b"data") {
+            Some((_, data)) if data.len() >= 8 => &data[8..],
+            _ => continue,
+        };
+        
+        match item {
+            b"\xA9nam" => set_audio_tag(&mut tags, "title", &String::from_utf8_lossy(value)),
+            b"\xA9ART" => set_audio_tag(&mut tags, "artist", &String::from_utf8_lossy(value)),
+            b"aART" => set_audio_tag(&mut tags, "album_artist", &String::from_utf8_lossy(value)),
+            b"\xA9alb" => set_audio_tag(&mut tags, "album", &String::from_utf8_lossy(value)),
+            b"\xA9day" => set_audio_tag(&mut tags, "year", &String::from_utf8_lossy(value)),
+            // Track and disc are binary: reserved u16, number u16, total u16
+            b"trkn" => tags.track = read_be_uint(value, 2, 2).map(|n| n as u32).filter(|&n| n > 0),
+            b"disk" => tags.disc = read_be_uint(value, 2, 2).map(|n| n as u32).filter(|&n| n > 0),
+            _ => {}
+        }
+    }
+    
+    Some(tags)
+}
+
+// Read music tags from an ID3v2-tagged, FLAC or MP4 audio file
+fn read_audio_tags(path: &Path) -> Option<AudioTags> {
+    let mut file = File::open(path).ok()?;
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic).ok()?;
+    file.rewind().ok()?;
+    
+    if magic.starts_with(b"ID3") {
+        read_id3v2(&mut file)
+    } else if magic.starts_with(b"fLaC") {
+        read_flac_tags(&mut file)
+    } else if &magic[4..8] == b"ftyp" {
+        read_mp4_tags(&mut file)
+    } else {
+        None
+    }
+}
+
+// Seconds between the MP4 epoch (1904) and the unix epoch
+const MP4_EPOCH_OFFSET: u64 = 2_082_844_800;
+
+// Seconds between the Matroska epoch (2001) and the unix epoch
+const MATROSKA_EPOCH_OFFSET: i64 = 978_307_200;
+
+// Friendly name for a container codec identifier
+fn codec_name(codec: &str) -> String {
+    match codec {
+        "avc1" | "avc3" | "V_MPEG4/ISO/AVC" => "H.264",
+        "hvc1" | "hev1" | "V_MPEGH/ISO/HEVC" => "HEVC",
+        "av01" | "V_AV1" => "AV1",
+        "vp09" | "V_VP9" => "VP9",
+        "vp08" | "V_VP8" => "VP8",
+        "mp4v" | "V_MPEG4/ISO/SP" | "V_MPEG4/ISO/ASP" => "MPEG-4",
+        "apch" | "apcn" | "apcs" | "apco" | "ap4h" => "ProRes",
+        other => other,
+    }
+    .to_string()
+}
+
+// Read creation time, duration and the first video track from an MP4/MOV moov box
+fn read_mp4_video(file: &mut File) -> Option<VideoInfo> {
+    let moov = read_top_level_box(file, b"moov", 64 * 1024 * 1024)?;
+    let mut info = VideoInfo::default();
+    
+    if let Some((_, mvhd)) = iso_boxes(&moov).into_iter().find(|(t, _)| *t == b"mvhd") {
+        let (created, timescale, duration) = if mvhd.first()? == &1 {
+            (read_be_uint(mvhd, 4, 8)?, read_be_uint(mvhd, 20, 4)?, read_be_uint(mvhd, 24, 8)?)
+        } else {
+            (read_be_uint(mvhd, 4, 4)?, read_be_uint(mvhd, 12, 4)?, read_be_uint(mvhd, 16, 4)?)
+        };
+        
+        // Zero means the writer didn't set a time; anything past tomorrow is garbage
+        info.created = created
+            .checked_sub(MP4_EPOCH_OFFSET)
+            .filter(|&created_unix| created > 0 && created_unix <= unix_now() + 86_400);
+        if timescale > 0 {
+            info.duration_secs = Some(duration as f64 / timescale as f64);
+        }
+    }
+    
+    for (_, trak) in iso_boxes(&moov).into_iter().filter(|(t, _)| *t == b"trak") {
+        let mdia = match iso_boxes(trak).into_iter().find(|(t, _)| *t == b"mdia") {
+            Some((_, mdia)) => mdia,
+            None => continue,
+        };
+        
+        let is_video = iso_boxes(mdia)
+            .into_iter()
+            .any(|(t, hdlr)| t == b"hdlr" && hdlr.get(8..12) == Some(b"vide"));
+        if !is_video {
+            continue;
+        }
+        
+        // mdia/minf/stbl/stsd holds one sample entry per codec configuration
+        let stsd = iso_boxes(mdia)
+            .into_iter()
+            .find(|(t, _)| *t == b"minf")
+            .and_then(|(_, minf)| iso_boxes(minf).into_iter().find(|(t, _)| *t == b"stbl"))
+            .and_then(|(_, stbl)| iso_boxes(stbl).into_iter().find(|(t, _)| *t == b"stsd"));
+        
+        if let Some((codec, entry)) = stsd.and_then(|(_, stsd)| iso_boxes(stsd.get(8..)?).into_iter().next()) {
+            info.codec = Some(codec_name(&String::from_utf8_lossy(codec)));
+            info.width = read_be_uint(entry, 24, 2).map(|w| w as u32).filter(|&w| w > 0);
+            info.height = read_be_uint(entry, 26, 2).map(|h| h as u32).filter(|&h| h > 0);
+        }
+        break;
+    }
+    
+    Some(info)
+}
+
+// Read an EBML variable-length integer, keeping the length marker for element IDs
+fn ebml_vint(data: &[u8], pos: usize, keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *data.get(pos)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+    
+    let mut value = if keep_marker { first as u64 } else { (first as u64) & (0xFF >> len) };
+    for &b in data.get(pos + 1..pos + len)? {
+        value = (value << 8) | b as u64;
+    }
+    
+    // A size with every value bit set means "unknown", i.e. until the end of the parent
+    if !keep_marker && value == (1u64 << (7 * len)) - 1 {
+        value = u64::MAX;
+    }
+    
+    Some((value, len))
+}
+
+// Iterate the EBML elements in `data` as (id, payload), stopping at the first cluster
+fn ebml_elements(data: &[u8]) -> Vec<(u64, &[u8])> {
+    let mut elements = Vec::new();
+    let mut pos = 0;
+    
+    while let Some((id, id_len)) = ebml_vint(data, pos, true) {
+        let (size, size_len) = match ebml_vint(data, pos + id_len, false) {
+            Some(size) => size,
+            None => break,
+        };
+        
+        // Clusters hold the media itself; headers come before them
+        if id == 0x1F43B675 {
+            break;
+        }
+        
+        let start = pos + id_len + size_len;
+        let end = start.saturating_add(size.min(usize::MAX as u64) as usize).min(data.len());
+        if start > data.len() {
+            break;
+        }
+        
+        elements.push((id, &data[start..end]));
+        pos = end;
+    }
+    
+    elements
+}
+
+// Read creation time, duration and the first video track from Matroska/WebM headers
+fn read_matroska_video(data: &[u8]) -> Option<VideoInfo> {
+    let segment = ebml_elements(data).into_iter().find(|(id, _)| *id == 0x18538067)?.1;
+    let mut info = VideoInfo::default();
+    
+    for (id, payload) in ebml_elements(segment) {
+        match id {
+            // Info
+            0x1549A966 => {
+                let mut timecode_scale = 1_000_000u64;
+                let mut duration = None;
+                
+                for (id, value) in ebml_elements(payload) {
+                    match id {
+                        // A malformed or zero scale keeps the default rather than losing the track info
+                        0x2AD7B1 if (1..=8).contains(&value.len()) => {
+                            timecode_scale = read_be_uint(value, 0, value.len()).filter(|&s| s > 0).unwrap_or(1_000_000)
+                        }
+                        0x4489 => {
+                            duration = match value.len() {
+                                4 => Some(f32::from_be_bytes(value.try_into().ok()?) as f64),
+                                8 => Some(f64::from_be_bytes(value.try_into().ok()?)),
+                                _ => None,
+                            }
+                        }
+                        0x4461 if value.len() == 8 => {
+                            let nanos = i64::from_be_bytes(value.try_into().ok()?);
+                            info.created = u64::try_from(MATROSKA_EPOCH_OFFSET + nanos / 1_000_000_000).ok();
+                        }
+                        _ => {}
+                    }
+                }
+                
+                info.duration_secs = duration.map(|d| d * timecode_scale as f64 / 1e9);
+            }
+            // Tracks
+            0x1654AE6B => {
+                for (_, entry) in ebml_elements(payload).into_iter().filter(|(id, _)| *id == 0xAE) {
+                    let fields = ebml_elements(entry);
+                    let is_video = fields.iter().any(|(id, v)| *id == 0x83 && v == &[1]);
+                    if !is_video || info.codec.is_some() {
+                        continue;
+                    }
+                    
+                    for (id, value) in fields {
+                        match id {
+                            0x86 => info.codec = Some(codec_name(String::from_utf8_lossy(value).trim_end_matches('\0'))),
+                            0xE0 => {
+                                for (id, dim) in ebml_elements(value) {
+                                    match id {
+                                        0xB0 => info.width = read_be_uint(dim, 0, dim.len()).map(|w| w as u32),
+                                        0xBA => info.height = read_be_uint(dim, 0, dim.len()).map(|h| h as u32),
+                                        _ => {}
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    Some(info)
+}
+
+// Read video metadata from an MP4/MOV or Matroska/WebM file
+fn read_video_info(path: &Path) -> Option<VideoInfo> {
+    let mut file = File::open(path).ok()?;
+    let mut data = Vec::new();
+    (&mut file).take(1024 * 1024).read_to_end(&mut data).ok()?;
+    
+    if data.get(4..8) == Some(b"ftyp") || data.get(4..8) == Some(b"moov") || data.get(4..8) == Some(b"wide") {
+        read_mp4_video(&mut file)
+    } else if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        read_matroska_video(&data)
+    } else {
+        None
+    }
+}
+
+// Parse a duration such as "90", "90s", "5m" or "1.5h" into seconds
+fn parse_duration(value: &str) -> Result<f64, Box<dyn Error>> {
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last() {
+        Some('s') => (&value[..value.len() - 1], 1.0),
+        Some('m') => (&value[..value.len() - 1], 60.0),
+        Some('h') => (&value[..value.len() - 1], 3600.0),
+        _ => (value, 1.0),
+    };
+    
+    let number: f64 = number
+        .parse()
+        .ok()
+        .filter(|number: &f64| number.is_finite() && *number >= 0.0)
+        .ok_or_else(|| format!("Invalid duration '{}' (use e.g. 90, 90s, 5m or 1.5h)", value))?;
+    
+    Ok(number * multiplier)
+}
+
+// Parse a size such as "2GB", "500 MB", "1.5T" or a plain byte count, in 1024-based units
+fn parse_size(value: &str) -> Result<u64, Box<dyn Error>> {
+    let value = value.trim();
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = (&value[..split], value[split..].trim().to_lowercase());
+    
+    let multiplier: u64 = match unit.trim_end_matches("ib").trim_end_matches('b') {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        "t" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(format!("Invalid size '{}' (use e.g. 1024, 500MB or 2GB)", value).into()),
+    };
+    
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}' (use e.g. 1024, 500MB or 2GB)", value))?;
+    
+    Ok((number * multiplier as f64) as u64)
+}
+
+// Parse an age such as "30d", "2w", "6mo" or "2y" into days (a plain number is days)
+fn parse_age(value: &str) -> Result<u64, Box<dyn Error>> {
+    let value = value.trim();
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = (&value[..split], value[split..].trim().to_lowercase());
+    
+    // Months and years as in the age report
+    let days = match unit.as_str() {
+        "" | "d" => 1.0,
+        "w" => 7.0,
+        "mo" => 30.0,
+        "y" => 365.0,
+        _ => return Err(format!("Invalid age '{}' (use e.g. 30d, 2w, 6mo or 2y)", value).into()),
+    };
+    
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid age '{}' (use e.g. 30d, 2w, 6mo or 2y)", value))?;
+    
+    Ok((number * days) as u64)
+}
+
+// Whether a file is a video at least `min_duration` seconds long
+fn meets_min_duration(file: &FileInfo, min_duration: f64) -> bool {
+    file.video
+        .as_ref()
+        .and_then(|video| video.duration_secs)
+        .is_some_and(|duration| duration >= min_duration)
+}
+
+// Decode the XML entities that appear in document metadata
+fn xml_unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = match rest[start..].find(';') {
+            Some(end) => start + end,
+            None => break,
+        };
+        
+        let entity = &rest[start + 1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        
+        match decoded {
+            Some(c) => result.push(c),
+            None => result.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    
+    result.push_str(rest);
+    result
+}
+
+// Find the opening tag `<prefix:name ...>` (any prefix) from `from`, returning (tag start, content start)
+fn xml_find_tag(xml: &str, name: &str, from: usize) -> Option<(usize, usize)> {
+    let mut pos = from;
+    
+    while let Some(offset) = xml[pos..].find('<') {
+        let start = pos + offset;
+        let tag_end = start + xml[start..].find('>')?;
+        let tag = &xml[start + 1..tag_end];
+        let tag_name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        
+        if tag_name.rsplit(':').next() == Some(name) {
+            return Some((start, tag_end + 1));
+        }
+        pos = start + 1;
+    }
+    
+    None
+}
+
+// Text of the first element with the given local name, with nested tags stripped
+fn xml_element_text(xml: &str, name: &str) -> Option<String> {
+    let (start, content_start) = xml_find_tag(xml, name, 0)?;
+    if xml[start..content_start].ends_with("/>") {
+        return None;
+    }
+    
+    // The closing tag reuses the opening tag's prefix
+    let tag_name = xml[start + 1..].split(|c: char| c.is_whitespace() || c == '>').next()?;
+    let content_end = content_start + xml[content_start..].find(&format!("</{}>", tag_name))?;
+    
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in xml[content_start..content_end].chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    
+    let text = xml_unescape(text.trim());
+    if text.is_empty() { None } else { Some(text) }
+}
+
+// Value of an attribute on the first element with the given local name
+fn xml_attribute(xml: &str, element: &str, attribute: &str) -> Option<String> {
+    let (start, content_start) = xml_find_tag(xml, element, 0)?;
+    let tag = &xml[start..content_start];
+    
+    tag.split_whitespace().find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key.rsplit(':').next() == Some(attribute))
+            .then(|| xml_unescape(value.trim_end_matches(['>', '/']).trim_matches(['"', '\''])))
+    })
+}
+
+// Parse the date formats found in document metadata into a timestamp
+fn parse_document_date(date: &str) -> Option<u64> {
+    let date = date.trim();
+    
+    let timestamp = if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(date) {
+        datetime.timestamp()
+    } else if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f") {
+        naive.and_local_timezone(Local).earliest()?.timestamp()
+    } else {
+        // Dates may be truncated to a year or month, as EPUB allows
+        let day = match date.len() {
+            4 => format!("{}-01-01", date),
+            7 => format!("{}-01", date),
+            _ => date.get(..10)?.to_string(),
+        };
+        let day = chrono::NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?;
+        day.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest()?.timestamp()
+    };
+    
+    u64::try_from(timestamp).ok()
+}
+
+// Parse a PDF date such as "D:20200131120000+01'00'"
+fn parse_pdf_date(date: &str) -> Option<u64> {
+    let date = date.trim().trim_start_matches("D:");
+    let digits: String = date.chars().take_while(|c| c.is_ascii_digit()).collect();
+    
+    // Missing fields default to the start of the period
+    let padded = format!("{}{}", digits, &"0101000000"[digits.len().saturating_sub(4).min(10)..]);
+    let naive = chrono::NaiveDateTime::parse_from_str(padded.get(..14)?, "%Y%m%d%H%M%S").ok()?;
+    
+    let zone = &date[digits.len()..];
+    let timestamp = match zone.chars().next() {
+        Some('Z') => naive.and_utc().timestamp(),
+        Some(sign @ ('+' | '-')) => {
+            let parts: Vec<i64> = zone[1..].split('\'').filter_map(|p| p.parse().ok()).collect();
+            let offset = parts.first().unwrap_or(&0) * 3600 + parts.get(1).unwrap_or(&0) * 60;
+            naive.and_utc().timestamp() - if sign == '+' { offset } else { -offset }
+        }
+        _ => naive.and_local_timezone(Local).earliest()?.timestamp(),
+    };
+    
+    u64::try_from(timestamp).ok()
+}
+
+// Position of `needle` in `haystack` at or after `from`
+fn find_bytes(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|p| p + from)
+}
+
+// Decode a PDF string object (literal or hex) starting at `data[0]`
+fn pdf_string(data: &[u8]) -> Option<String> {
+    let mut bytes = Vec::new();
+    
+    match data.first()? {
+        b'(' => {
+            let mut depth = 0;
+            let mut i = 0;
+            while i < data.len() {
+                let b = data[i];
+                match b {
+                    b'\\' => {
+                        i += 1;
+                        match *data.get(i)? {
+                            b'n' => bytes.push(b'\n'),
+                            b'r' => bytes.push(b'\r'),
+                            b't' => bytes.push(b'\t'),
+                            b'b' => bytes.push(8),
+                            b'f' => bytes.push(12),
+                            b'0'..=b'7' => {
+                                let octal: Vec<u8> = data[i..].iter().take(3).take_while(|b| (b'0'..=b'7').contains(b)).copied().collect();
+                                bytes.push(octal.iter().fold(0u8, |acc, &o| acc.wrapping_mul(8).wrapping_add(o - b'0')));
+                                i += octal.len() - 1;
+                            }
+                            b'\r' | b'\n' => {}
+                            other => bytes.push(other),
+                        }
+                    }
+                    b'(' => {
+                        if depth > 0 {
+                            bytes.push(b);
+                        }
+                        depth += 1;
+                    }
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                        bytes.push(b);
+                    }
+                    _ => bytes.push(b),
+                }
+                i += 1;
+            }
+        }
+        b'<' => {
+            let end = data.iter().position(|&b| b == b'>')?;
+            let hex: Vec<u8> = data[1..end].iter().copied().filter(|b| b.is_ascii_hexdigit()).collect();
+            for pair in hex.chunks(2) {
+                let digits = std::str::from_utf8(pair).ok()?;
+                bytes.push(u8::from_str_radix(&format!("{:0<2}", digits), 16).ok()?);
+            }
+        }
+        _ => return None,
+    }
+    
+    // Text strings are either UTF-16BE with a BOM or PDFDocEncoding (close to Latin-1)
+    let text = match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&b| b as char).collect(),
+    };
+    
+    let text = text.trim().to_string();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+// String value of a key such as "/Title" in a PDF dictionary
+fn pdf_dict_string(dict: &[u8], key: &[u8]) -> Option<String> {
+    let mut pos = 0;
+    
+    while let Some(found) = find_bytes(dict, key, pos) {
+        let after = found + key.len();
+        // Skip keys that merely start with this one, e.g. /Title vs /TitleX
+        if dict.get(after).is_some_and(|b| b.is_ascii_alphanumeric()) {
+            pos = after;
+            continue;
+        }
+        
+        let value_start = after + dict[after..].iter().position(|b| !b.is_ascii_whitespace())?;
+        return pdf_string(&dict[value_start..]);
+    }
+    
+    None
+}
+
+// Body of indirect object `number generation obj` in a PDF
+fn pdf_object<'a>(data: &'a [u8], number: &str, generation: &str) -> Option<&'a [u8]> {
+    let header = format!("{} {} obj", number, generation);
+    let mut pos = 0;
+    
+    while let Some(found) = find_bytes(data, header.as_bytes(), pos) {
+        // Don't match "12 0 obj" when looking for "2 0 obj"
+        if found == 0 || !data[found - 1].is_ascii_digit() {
+            let start = found + header.len();
+            let end = find_bytes(data, b"endobj", start).unwrap_or(data.len());
+            return Some(&data[start..end]);
+        }
+        pos = found + 1;
+    }
+    
+    None
+}
+
+// Read the Info dictionary, page count and XMP packet of a PDF
+fn read_pdf_info(path: &Path) -> Option<DocumentInfo> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    
+    // Metadata lives near the start (linearized files, XMP) or the end (trailer)
+    let mut data = Vec::new();
+    (&mut file).take(4 * 1024 * 1024).read_to_end(&mut data).ok()?;
+    if len > data.len() as u64 {
+        file.seek(std::io::SeekFrom::Start(len.saturating_sub(1024 * 1024).max(data.len() as u64))).ok()?;
+        file.read_to_end(&mut data).ok()?;
+    }
+    
+    let mut info = DocumentInfo::default();
+    
+    // The last /Info reference belongs to the latest incremental update
+    let info_dict = (0..data.len())
+        .rev()
+        .find(|&i| data[i..].starts_with(b"/Info") && !data.get(i + 5).is_some_and(|b| b.is_ascii_alphanumeric()))
+        .and_then(|i| {
+            let reference = String::from_utf8_lossy(&data[i + 5..(i + 40).min(data.len())]).to_string();
+            let mut parts = reference.split_whitespace();
+            let (number, generation) = (parts.next()?.to_string(), parts.next()?.to_string());
+            pdf_object(&data, &number, &generation)
+        });
+    
+    if let Some(dict) = info_dict {
+        info.title = pdf_dict_string(dict, b"/Title");
+        info.author = pdf_dict_string(dict, b"/Author");
+        info.created = pdf_dict_string(dict, b"/CreationDate").and_then(|d| parse_pdf_date(&d));
+    }
+    info.language = pdf_dict_string(&data, b"/Lang");
+    
+    // The root page tree has the largest /Count of all /Type /Pages nodes
+    let mut pos = 0;
+    while let Some(found) = find_bytes(&data, b"/Pages", pos) {
+        pos = found + 6;
+        let before = &data[found.saturating_sub(16)..found];
+        let is_type = String::from_utf8_lossy(before).trim_end().ends_with("/Type");
+        if !is_type || data.get(pos).is_some_and(|b| b.is_ascii_alphanumeric()) {
+            continue;
+        }
+        
+        let object_start = data[..found].windows(3).rposition(|w| w == b"obj").unwrap_or(0);
+        let object_end = find_bytes(&data, b"endobj", found).unwrap_or(data.len());
+        let object = &data[object_start..object_end];
+        
+        if let Some(count_at) = find_bytes(object, b"/Count", 0) {
+            let count: String = String::from_utf8_lossy(&object[count_at + 6..])
+                .trim_start()
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            if let Ok(count) = count.parse::<u32>() {
+                info.pages = Some(info.pages.map_or(count, |pages| pages.max(count)));
+            }
+        }
+    }
+    
+    // XMP fills in anything the Info dictionary lacks
+    if let Some(start) = find_bytes(&data, b"<x:xmpmeta", 0) {
+        let end = find_bytes(&data, b"</x:xmpmeta>", start).unwrap_or(data.len());
+        let xmp = String::from_utf8_lossy(&data[start..end]);
+        
+        info.title = info.title.or_else(|| xml_element_text(&xmp, "title"));
+        info.author = info.author.or_else(|| xml_element_text(&xmp, "creator"));
+        info.created = info.created.or_else(|| xml_element_text(&xmp, "CreateDate").and_then(|d| parse_document_date(&d)));
+        info.language = info.language.or_else(|| xml_element_text(&xmp, "language"));
+    }
+    
+    Some(info)
+}
+
+// Read a text entry from a zip-based document
+fn read_zip_text(archive: &mut zip::ZipArchive<File>, name: &str) -> Option<String> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut text = String::new();
+    (&mut entry).take(4 * 1024 * 1024).read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+// Read metadata from an OOXML, ODF or EPUB package
+fn read_package_info(path: &Path) -> Option<DocumentInfo> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    let mut info = DocumentInfo::default();
+    
+    if let Some(core) = read_zip_text(&mut archive, "docProps/core.xml") {
+        // OOXML: Dublin Core properties, page or slide count in app.xml
+        info.title = xml_element_text(&core, "title");
+        info.author = xml_element_text(&core, "creator");
+        info.created = xml_element_text(&core, "created").and_then(|d| parse_document_date(&d));
+        info.language = xml_element_text(&core, "language");
+        
+        if let Some(app) = read_zip_text(&mut archive, "docProps/app.xml") {
+            info.pages = xml_element_text(&app, "Pages")
+                .or_else(|| xml_element_text(&app, "Slides"))
+                .and_then(|n| n.parse().ok());
+        }
+    } else if let Some(meta) = read_zip_text(&mut archive, "meta.xml") {
+        // ODF: office:meta with document statistics
+        info.title = xml_element_text(&meta, "title");
+        info.author = xml_element_text(&meta, "initial-creator").or_else(|| xml_element_text(&meta, "creator"));
+        info.created = xml_element_text(&meta, "creation-date").and_then(|d| parse_document_date(&d));
+        info.language = xml_element_text(&meta, "language");
+        info.pages = xml_attribute(&meta, "document-statistic", "page-count").and_then(|n| n.parse().ok());
+    } else if let Some(container) = read_zip_text(&mut archive, "META-INF/container.xml") {
+        // EPUB: the container points at the OPF package document
+        let opf_path = xml_attribute(&container, "rootfile", "full-path")?;
+        let opf = read_zip_text(&mut archive, &opf_path)?;
+        
+        info.title = xml_element_text(&opf, "title");
+        info.author = xml_element_text(&opf, "creator");
+        info.created = xml_element_text(&opf, "date").and_then(|d| parse_document_date(&d));
+        info.language = xml_element_text(&opf, "language");
+    } else {
+        return None;
+    }
+    
+    Some(info)
+}
+
+// Read document metadata from a PDF, OOXML, ODF or EPUB file
+fn read_document_info(path: &Path) -> Option<DocumentInfo> {
+    let mut magic = [0u8; 4];
+    File::open(path).ok()?.read_exact(&mut magic).ok()?;
+    
+    match &magic {
+        b"%PDF" => read_pdf_info(path),
+        b"PK\x03\x04" => read_package_info(path),
+        _ => None,
+    }
+}
+
+// Placeholders available in organize templates
+const TEMPLATE_FIELDS: &[&str] = &[
+    "artist", "album_artist", "album", "title", "track", "disc", "year", "author", "language", "name", "ext",
+];
+
+// Default template for --by music
+const MUSIC_TEMPLATE: &str = "{album_artist}/{album}/{track:02} - {title}.{ext}";
+
+// Make a metadata value safe to use as a single path component
+fn sanitize_component(value: &str) -> Option<String> {
+    let cleaned: String = value
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').trim();
+    
+    if cleaned.is_empty() { None } else { Some(cleaned.to_string()) }
+}
+
+// Value of a template placeholder for a file, if known
+fn template_value(file: &FileInfo, field: &str) -> Option<String> {
+    let audio = file.audio.as_ref();
+    let document = file.document.as_ref();
+    
+    let value = match field {
+        "artist" => audio?.artist.clone(),
+        // Album artist falls back to the track artist, as music players do
+        "album_artist" => audio?.album_artist.clone().or_else(|| audio?.artist.clone()),
+        "album" => audio?.album.clone(),
+        "title" => audio.and_then(|a| a.title.clone()).or_else(|| document?.title.clone()),
+        "track" => audio?.track.map(|n| n.to_string()),
+        "disc" => audio?.disc.map(|n| n.to_string()),
+        "year" => audio.and_then(|a| a.year).map(|n| n.to_string()).or_else(|| {
+            let created = DateTime::<Utc>::from_timestamp(document?.created? as i64, 0)?;
+            Some(created.with_timezone(&Local).format("%Y").to_string())
+        }),
+        "author" => document?.author.clone(),
+        "language" => document?.language.clone(),
+        "name" => Some(split_extension(&file.path).0),
+        "ext" => split_extension(&file.path).1,
+        _ => None,
+    };
+    
+    sanitize_component(&value?)
+}
+
+// Literal text followed by an optional (field, zero-pad width) placeholder
+type TemplatePart = (String, Option<(String, usize)>);
+
+// Split a template into literal text and placeholders
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, Box<dyn Error>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}').ok_or("Unclosed '{' in template")? + start;
+        let (field, width) = match rest[start + 1..end].split_once(':') {
+            Some((field, width)) => (field, width.parse().map_err(|_| format!("Invalid width in {{{}}}", &rest[start + 1..end]))?),
+            None => (&rest[start + 1..end], 0),
+        };
+        
+        if !TEMPLATE_FIELDS.contains(&field) {
+            return Err(format!("Unknown template placeholder {{{}}} (available: {})", field, TEMPLATE_FIELDS.join(", ")).into());
+        }
+        
+        parts.push((rest[..start].to_string(), Some((field.to_string(), width))));
+        rest = &rest[end + 1..];
+    }
+    
+    parts.push((rest.to_string(), None));
+    Ok(parts)
+}
+
+// Render a template into a relative path, or list the placeholders the file has no value for
+fn render_template(template: &str, file: &FileInfo) -> Result<PathBuf, Vec<String>> {
+    let parts = parse_template(template).map_err(|e| vec![e.to_string()])?;
+    let mut rendered = String::new();
+    let mut missing = Vec::new();
+    
+    for (literal, placeholder) in parts {
+        rendered.push_str(&literal);
+        
+        if let Some((field, width)) = placeholder {
+            match template_value(file, &field) {
+                Some(value) => rendered.push_str(&format!("{:0>width$}", value, width = width)),
+                None => missing.push(field),
+            }
+        }
+    }
+    
+    if missing.is_empty() { Ok(PathBuf::from(rendered)) } else { Err(missing) }
+}
+
+// Number of differing bits between two perceptual hashes
+fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// Get file info including size, modification time, and category
+fn get_file_info(path: &Path, config: &TidyConfig, options: &ScanOptions) -> Result<FileInfo, Box<dyn Error>> {
+    let metadata = fs::metadata(path)?;
+    let size = metadata.len();
+    
+    let last_modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    
+    let Classification {
+        category,
+        subcategory,
+        source: category_source,
+        content,
+    } = classify_file(path, config);
+    
+    let hash = if options.calculate_hashes {
+        if size >= config.mmap_hash_threshold {
+            Some(calculate_hash_mmap(path)?)
+        } else {
+            Some(calculate_hash(path)?)
+        }
+    } else {
+        None
+    };
+    
+    // Undecodable images simply get no perceptual hash rather than failing the scan
+    let (perceptual_hash, dimensions) = match category {
+        FileCategory::Image if options.calculate_perceptual_hashes => match calculate_perceptual_hash(path) {
+            Ok((phash, dims)) => (Some(phash), Some(dims)),
+            Err(_) => (None, None),
+        },
+        _ => (None, None),
+    };
+    
+    let exif = match category {
+        FileCategory::Image if options.read_metadata.exif => read_exif(path),
+        _ => None,
+    };
+    
+    let audio = match category {
+        FileCategory::Audio if options.read_metadata.audio => read_audio_tags(path),
+        _ => None,
+    };
+    
+    let video = match category {
+        FileCategory::Video if options.read_metadata.video => read_video_info(path),
+        _ => None,
+    };
+    
+    let document = match category {
+        FileCategory::Document if options.read_metadata.document => read_document_info(path),
+        _ => None,
+    };
+    
+    Ok(FileInfo {
+        path: path.to_path_buf(),
+        size,
+        last_modified,
+        category,
+        subcategory,
+        category_source,
+        content,
+        hash,
+        perceptual_hash,
+        dimensions,
+        exif,
+        audio,
+        video,
+        document,
+    })
+}
+
+// Return the first project marker present in a directory, if any
+fn find_project_marker(dir: &Path, config: &TidyConfig) -> Option<String> {
+    for marker in &config.project_markers {
+        if marker.contains(['*', '?', '[']) {
+            let pattern = match glob::Pattern::new(marker) {
+                Ok(pattern) => pattern,
+                Err(_) => continue,
+            };
+            let found = fs::read_dir(dir).ok()?.filter_map(|e| e.ok()).any(|e| {
+                pattern.matches(&e.file_name().to_string_lossy())
+            });
+            if found {
+                return Some(marker.clone());
+            }
+        } else if dir.join(marker).exists() {
+            return Some(marker.clone());
+        }
+    }
+    
+    None
+}
+
+// Friendly project kind for a marker, used as the project's subcategory
+fn project_kind(marker: &str) -> String {
+    match marker {
+        "Cargo.toml" => "Rust".to_string(),
+        "package.json" => "Node".to_string(),
+        "pyproject.toml" => "Python".to_string(),
+        "go.mod" => "Go".to_string(),
+        "*.sln" => ".NET".to_string(),
+        ".git" => "Git".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// Collect a project directory as a single item with its total size and newest mtime,
+// along with the number of entries inside it that could not be read
+fn get_project_info(dir: &Path, marker: &str) -> (FileInfo, usize) {
+    let mut size = 0;
+    let mut last_modified = 0;
+    let mut errors = 0;
+    
+    for entry in WalkDir::new(dir) {
+        // One unreadable file shouldn't drop the whole project from the scan
+        let metadata = match entry.and_then(|e| e.metadata()) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                errors += 1;
+                continue;
+            }
+        };
+        
+        if metadata.is_file() {
+            size += metadata.len();
+            
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default()
+                .as_secs();
+            last_modified = last_modified.max(modified);
+        }
+    }
+    
+    let info = FileInfo {
+        path: dir.to_path_buf(),
+        size,
+        last_modified,
+        category: FileCategory::Project,
+        subcategory: Some(project_kind(marker)),
+        category_source: CategorySource::Content,
+        content: None,
+        hash: None,
+        perceptual_hash: None,
+        dimensions: None,
+        exif: None,
+        audio: None,
+        video: None,
+        document: None,
+    };
+    
+    (info, errors)
+}
+
+// Scan directory and collect file information
+fn scan_directory(
+    dir: &Path, 
+    config: &TidyConfig, 
+    options: &ScanOptions,
+) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    Ok(scan_directory_with_errors(dir, config, options)?.0)
+}
+
+// Scan a directory, also returning how many entries could not be read
+fn scan_directory_with_errors(
+    dir: &Path,
+    config: &TidyConfig,
+    options: &ScanOptions,
+) -> Result<(Vec<FileInfo>, usize), Box<dyn Error>> {
+    let pb = if options.quiet { ProgressBar::hidden() } else { ProgressBar::new_spinner() };
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .unwrap()
+            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈"),
+    );
+    pb.set_message("Scanning directory...");
+    
+    let files_info = Arc::new(Mutex::new(Vec::new()));
+    let error_count = Arc::new(Mutex::new(0));
+    let file_count = Arc::new(Mutex::new(0));
+    let hashed_bytes = Arc::new(Mutex::new(0u64));
+    
+    let walker = if options.recursive {
+        WalkDir::new(dir)
+    } else {
+        WalkDir::new(dir).max_depth(1)
+    };
+    
+    // Project roots below the scanned directory are collected whole instead of descended into
+    let mut project_roots: Vec<(PathBuf, String)> = Vec::new();
+    let mut walk_errors = 0;
+    
+    let entries: Vec<_> = walker
+        .into_iter()
+        .filter_entry(|e| {
+            let path = e.path();
+            if config.ignore_patterns.iter().any(|pattern| {
+                path.to_string_lossy().contains(pattern)
+            }) {
+                return false;
+            }
+            
+            if options.detect_projects && options.recursive && e.depth() > 0 && e.file_type().is_dir() {
+                if let Some(marker) = find_project_marker(path, config) {
+                    project_roots.push((path.to_path_buf(), marker));
+                    return false;
+                }
+            }
+            
+            true
+        })
+        .filter_map(|e| e.map_err(|_| walk_errors += 1).ok())
+        .collect();
+    *error_count.lock().unwrap() += walk_errors;
+    
+    project_roots.par_iter().for_each(|(root, marker)| {
+        let (info, errors) = get_project_info(root, marker);
+        files_info.lock().unwrap().push(info);
+        *error_count.lock().unwrap() += errors;
+    });
+    
+    pb.set_length(entries.len() as u64);
+    pb.set_message("Processing files...");
+    
+    let processing_start = Instant::now();
+    
+    entries.into_par_iter().for_each(|entry| {
+        let path = entry.path();
+        if path.is_file() {
+            match get_file_info(path, config, options) {
+                Ok(info) => {
+                    if info.hash.is_some() {
+                        *hashed_bytes.lock().unwrap() += info.size;
+                    }
+                    
+                    let mut file_infos = files_info.lock().unwrap();
+                    file_infos.push(info);
+                    
+                    let mut count = file_count.lock().unwrap();
+                    *count += 1;
+                    if *count % 100 == 0 {
+                        pb.set_message(format!("Processed {} files...", *count));
+                    }
+                }
+                Err(_) => {
+                    let mut errors = error_count.lock().unwrap();
+                    *errors += 1;
+                }
+            }
+        }
+    });
+    
+    let error_count = *error_count.lock().unwrap();
+    let file_count = *file_count.lock().unwrap();
+    let hashed_bytes = *hashed_bytes.lock().unwrap();
+    
+    let throughput = if options.calculate_hashes && hashed_bytes > 0 {
+        let seconds = processing_start.elapsed().as_secs_f64().max(0.001);
+        format!(
+            ", hashed {} at {}/s",
+            format_size(hashed_bytes),
+            format_size((hashed_bytes as f64 / seconds) as u64)
+        )
+    } else {
+        String::new()
+    };
+    
+    let projects = if project_roots.is_empty() {
+        String::new()
+    } else {
+        format!(" and {} projects", project_roots.len())
+    };
+    
+    pb.finish_with_message(format!(
+        "Scan complete. Processed {} files{} with {} errors{}",
+        file_count, projects, error_count, throughput
+    ));
+    
+    Ok((Arc::try_unwrap(files_info).unwrap().into_inner()?, error_count))
+}
+
+// Duplicate files grouped by content hash
+type DuplicateGroups<'a> = HashMap<String, Vec<&'a FileInfo>>;
+
+// Find duplicate files based on hash
+fn find_duplicates(files: &[FileInfo]) -> DuplicateGroups<'_> {
+    let mut duplicates: DuplicateGroups = HashMap::new();
+    
+    for file in files {
+        if let Some(hash) = &file.hash {
+            duplicates.entry(hash.clone()).or_default().push(file);
+        }
+    }
+    
+    // Keep only entries with more than one file (actual duplicates)
+    duplicates.retain(|_, files| files.len() > 1);
+    
+    // Scans finish in any order, so sort to keep the first file (the keeper) stable between runs
+    for group in duplicates.values_mut() {
+        group.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    
+    duplicates
+}
+
+// Check that a file still has the size and modification time recorded during the scan
+fn unchanged_since_scan(file: &FileInfo) -> bool {
+    match fs::metadata(&file.path) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+            metadata.len() == file.size && modified == Some(file.last_modified)
+        }
+        Err(_) => false,
+    }
+}
+
+// Compare two files byte for byte
+fn files_identical(a: &Path, b: &Path) -> Result<bool, Box<dyn Error>> {
+    let mut file_a = File::open(a)?;
+    let mut file_b = File::open(b)?;
+    
+    if file_a.metadata()?.len() != file_b.metadata()?.len() {
+        return Ok(false);
+    }
+    
+    let mut buffer_a = [0; 8192];
+    let mut buffer_b = [0; 8192];
+    
+    loop {
+        let bytes_read = file_a.read(&mut buffer_a)?;
+        if bytes_read == 0 {
+            return Ok(true);
+        }
+        file_b.read_exact(&mut buffer_b[..bytes_read])?;
+        if buffer_a[..bytes_read] != buffer_b[..bytes_read] {
+            return Ok(false);
+        }
+    }
+}
+
+// Byte-compare every duplicate against its group keeper (the first file), dropping
+// files that changed since the scan or don't match. Returns the confirmed groups
+// and the skipped files with the reason they were skipped.
+fn verify_duplicates(duplicates: DuplicateGroups<'_>) -> (DuplicateGroups<'_>, Vec<(&FileInfo, String)>) {
+    let mut confirmed = HashMap::new();
+    let mut skipped = Vec::new();
+    
+    for (hash, group) in duplicates {
+        let keeper = group[0];
+        
+        if !unchanged_since_scan(keeper) {
+            for file in group {
+                skipped.push((file, "keeper changed since scan".to_string()));
+            }
+            continue;
+        }
+        
+        let mut verified = vec![keeper];
+        
+        for file in group.into_iter().skip(1) {
+            if !unchanged_since_scan(file) {
+                skipped.push((file, "changed since scan".to_string()));
+                continue;
+            }
+            
+            match files_identical(&keeper.path, &file.path) {
+                Ok(true) => verified.push(file),
+                Ok(false) => skipped.push((file, "content differs from keeper".to_string())),
+                Err(e) => skipped.push((file, format!("could not compare: {}", e))),
+            }
+        }
+        
+        if verified.len() > 1 {
+            confirmed.insert(hash, verified);
+        }
+    }
+    
+    (confirmed, skipped)
+}
+
+// Build report groups for every duplicate group, largest waste first.
+// Files are listed by path, and the first one is the copy the .sh script keeps
+fn build_duplicate_report(duplicates: &DuplicateGroups) -> Vec<DuplicateReportGroup> {
+    let mut groups: Vec<DuplicateReportGroup> = duplicates
+        .iter()
+        .map(|(hash, group)| {
+            let mut files: Vec<DuplicateReportFile> = group
+                .iter()
+                .map(|f| DuplicateReportFile {
+                    path: f.path.clone(),
+                    last_modified: f.last_modified,
+                })
+                .collect();
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            
+            DuplicateReportGroup {
+                hash: hash.clone(),
+                size: group[0].size,
+                wasted_bytes: group[0].size * (files.len() as u64 - 1),
+                files,
+            }
+        })
+        .collect();
+    
+    groups.sort_by(|a, b| b.wasted_bytes.cmp(&a.wasted_bytes).then_with(|| a.hash.cmp(&b.hash)));
+    groups
+}
+
+// Quote a CSV field if it contains separators, quotes or newlines
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Quote a path for a POSIX shell, or None if it contains control characters
+// that could end a comment line and turn the rest of the name into a command
+fn shell_quote(value: &str) -> Option<String> {
+    if value.chars().any(char::is_control) {
+        return None;
+    }
+    Some(format!("'{}'", value.replace('\'', "'\\''")))
+}
+
+// Pick the duplicate report format from the file extension
+fn duplicate_report_format(path: &Path) -> Result<String, Box<dyn Error>> {
+    let format = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    match format.as_str() {
+        "json" | "csv" | "sh" => Ok(format),
+        _ => Err("Report file must end in .json, .csv or .sh".into()),
+    }
+}
+
+// Write every duplicate group to a JSON, CSV or shell script report, chosen by extension
+fn write_duplicate_report(duplicates: &DuplicateGroups, path: &Path) -> Result<(), Box<dyn Error>> {
+    let groups = build_duplicate_report(duplicates);
+    let format = duplicate_report_format(path)?;
+    
+    let contents = match format.as_str() {
+        "json" => serde_json::to_string_pretty(&groups)?,
+        "csv" => {
+            let mut out = String::from("group,hash,size,wasted_bytes,last_modified,path\n");
+            for (i, group) in groups.iter().enumerate() {
+                for file in &group.files {
+                    out.push_str(&format!(
+                        "{},{},{},{},{},{}\n",
+                        i + 1,
+                        group.hash,
+                        group.size,
+                        group.wasted_bytes,
+                        file.last_modified,
+                        csv_escape(&file.path.to_string_lossy())
+                    ));
+                }
+            }
+            out
+        }
+        // Only .sh is left after duplicate_report_format
+        _ => {
+            let wasted: u64 = groups.iter().map(|g| g.wasted_bytes).sum();
+            let mut out = String::from("#!/bin/sh\n");
+            out.push_str("# Duplicate removal script generated by tidyfs\n");
+            out.push_str(&format!(
+                "# {} groups, {} reclaimable. Review and uncomment the rm lines to delete.\n",
+                groups.len(),
+                format_size(wasted)
+            ));
+            out.push_str("# The first path of each group, in sorted order, is the one kept.\n");
+            
+            for (i, group) in groups.iter().enumerate() {
+                out.push_str(&format!(
+                    "\n# Group {}: {} ({} each, wasting {})\n",
+                    i + 1,
+                    group.hash,
+                    format_size(group.size),
+                    format_size(group.wasted_bytes)
+                ));
+                for (j, file) in group.files.iter().enumerate() {
+                    let name = file.path.to_string_lossy();
+                    let Some(quoted) = shell_quote(&name) else {
+                        eprintln!("Warning: leaving {:?} out of the script, its name contains control characters", name);
+                        out.push_str(&format!("# skipped {}, name contains control characters\n", name.escape_debug()));
+                        continue;
+                    };
+                    if j == 0 {
+                        out.push_str(&format!("# keep {}\n", quoted));
+                    } else {
+                        out.push_str(&format!("# rm -- {}\n", quoted));
+                    }
+                }
+            }
+            out
+        }
+    };
+    
+    let mut file = File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    
+    Ok(())
+}
+
+// Group images whose perceptual hashes are within `threshold` bits of each other
+fn find_similar_images(files: &[FileInfo], threshold: u32) -> Vec<Vec<&FileInfo>> {
+    let images: Vec<(&FileInfo, u64)> = files
+        .iter()
+        .filter_map(|f| f.perceptual_hash.map(|h| (f, h)))
+        .collect();
+    
+    // Union-find over every pair that is close enough, so A~B and B~C end up together
+    let mut parent: Vec<usize> = (0..images.len()).collect();
+    
+    fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    
+    for i in 0..images.len() {
+        for j in (i + 1)..images.len() {
+            if hamming_distance(images[i].1, images[j].1) <= threshold {
+                let root_i = find_root(&mut parent, i);
+                let root_j = find_root(&mut parent, j);
+                if root_i != root_j {
+                    parent[root_j] = root_i;
+                }
+            }
+        }
+    }
+    
+    let mut groups: HashMap<usize, Vec<&FileInfo>> = HashMap::new();
+    for (i, (file, _)) in images.iter().enumerate() {
+        let root = find_root(&mut parent, i);
+        groups.entry(root).or_default().push(*file);
+    }
+    
+    let mut groups: Vec<Vec<&FileInfo>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    
+    // Best copy first: highest resolution, then largest file
+    for group in &mut groups {
+        group.sort_by_key(|f| {
+            let pixels = f.dimensions.map(|(w, h)| w as u64 * h as u64).unwrap_or(0);
+            std::cmp::Reverse((pixels, f.size))
+        });
+    }
+    
+    groups.sort_by_key(|group| std::cmp::Reverse(group.iter().map(|f| f.size).sum::<u64>()));
+    
+    groups
+}
+
+// Compare two scanned trees by relative path and content hash
+fn diff_trees(a_root: &Path, a_files: &[FileInfo], b_root: &Path, b_files: &[FileInfo]) -> TreeDiff {
+    let relative = |root: &Path, file: &FileInfo| {
+        file.path.strip_prefix(root).unwrap_or(&file.path).to_path_buf()
+    };
+    
+    let a_by_path: HashMap<PathBuf, &FileInfo> = a_files.iter().map(|f| (relative(a_root, f), f)).collect();
+    let b_by_path: HashMap<PathBuf, &FileInfo> = b_files.iter().map(|f| (relative(b_root, f), f)).collect();
+    
+    let mut b_by_hash: HashMap<&str, Vec<PathBuf>> = HashMap::new();
+    for (path, file) in &b_by_path {
+        if let Some(hash) = &file.hash {
+            b_by_hash.entry(hash.as_str()).or_default().push(path.clone());
+        }
+    }
+    
+    let a_hashes: HashSet<&str> = a_files.iter().filter_map(|f| f.hash.as_deref()).collect();
+    
+    let mut diff = TreeDiff {
+        only_in_a: Vec::new(),
+        only_in_b: Vec::new(),
+        moved: Vec::new(),
+        modified: Vec::new(),
+        identical: 0,
+        a_errors: 0,
+        b_errors: 0,
+        a_contained_in_b: true,
+    };
+    
+    for (path, a_file) in &a_by_path {
+        let b_matches = a_file.hash.as_deref().and_then(|h| b_by_hash.get(h));
+        
+        if b_matches.is_none() {
+            diff.a_contained_in_b = false;
+        }
+        
+        match b_by_path.get(path) {
+            Some(b_file) if b_file.hash == a_file.hash => diff.identical += 1,
+            Some(_) => diff.modified.push(path.clone()),
+            None => match b_matches {
+                Some(b_paths) => diff.moved.push(MovedFile {
+                    a_path: path.clone(),
+                    b_path: b_paths.iter().min().unwrap().clone(),
+                }),
+                None => diff.only_in_a.push(path.clone()),
+            },
+        }
+    }
+    
+    for (path, b_file) in &b_by_path {
+        let content_in_a = b_file.hash.as_deref().is_some_and(|h| a_hashes.contains(h));
+        if !a_by_path.contains_key(path) && !content_in_a {
+            diff.only_in_b.push(path.clone());
+        }
+    }
+    
+    diff.only_in_a.sort();
+    diff.only_in_b.sort();
+    diff.moved.sort_by(|x, y| x.a_path.cmp(&y.a_path));
+    diff.modified.sort();
+    
+    diff
+}
+
+// Display the result of a tree comparison
+fn display_tree_diff(diff: &TreeDiff, a_root: &Path, b_root: &Path) {
+    println!("\n{}", "Tree Comparison".bold().underline());
+    println!("A: {}", a_root.display());
+    println!("B: {}", b_root.display());
+    
+    println!("\nIdentical files: {}", diff.identical);
+    
+    if diff.a_errors > 0 || diff.b_errors > 0 {
+        println!(
+            "{}",
+            format!("Unreadable entries: {} in A, {} in B", diff.a_errors, diff.b_errors).yellow()
+        );
+    }
+    
+    let sections = [
+        ("Only in A", &diff.only_in_a),
+        ("Only in B", &diff.only_in_b),
+        ("Same path, different content", &diff.modified),
+    ];
+    
+    for (title, paths) in sections {
+        println!("\n{} ({}):", title.bold().yellow(), paths.len());
+        for path in paths {
+            println!("  {}", path.display());
+        }
+    }
+    
+    println!("\n{} ({}):", "Same content, different path".bold().yellow(), diff.moved.len());
+    for moved in &diff.moved {
+        println!("  {} -> {}", moved.a_path.display(), moved.b_path.display());
+    }
+    
+    if diff.a_contained_in_b {
+        println!("\n{}", "Everything in A is present in B.".bold().green());
+    } else if diff.a_errors > 0 {
+        println!("\n{}", "Some entries in A could not be read, so it cannot be verified.".bold().red());
+    } else {
+        println!("\n{}", "Some content in A is missing from B.".bold().red());
+    }
+}
+
+// Record the files of a scan relative to its root
+fn snapshot_from_files(root: &Path, files: &[FileInfo]) -> Snapshot {
+    // Store an absolute root so snapshots from different working directories line up
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    
+    let mut snapshot_files: Vec<SnapshotFile> = files
+        .iter()
+        .map(|file| SnapshotFile {
+            path: file.path.strip_prefix(root).unwrap_or(&file.path).to_path_buf(),
+            size: file.size,
+            last_modified: file.last_modified,
+            hash: file.hash.clone(),
+            category: category_label(file, false),
+        })
+        .collect();
+    snapshot_files.sort_by(|a, b| a.path.cmp(&b.path));
+    
+    Snapshot {
+        schema_version: SNAPSHOT_SCHEMA_VERSION,
+        root: canonical_root,
+        created: unix_now(),
+        files: snapshot_files,
+    }
+}
+
+// Directory of a snapshot path, cut to `depth` levels ("." for files at the root)
+fn snapshot_directory(path: &Path, depth: usize) -> String {
+    let components: Vec<String> = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .take(depth)
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    
+    if components.is_empty() {
+        ".".to_string()
+    } else {
+        components.join("/")
+    }
+}
+
+// Pair per-name totals from two snapshots, keeping only names whose size changed
+fn size_deltas(before: HashMap<String, u64>, after: HashMap<String, u64>) -> Vec<SizeDelta> {
+    let names: HashSet<&String> = before.keys().chain(after.keys()).collect();
+    
+    let mut deltas: Vec<SizeDelta> = names
+        .into_iter()
+        .map(|name| SizeDelta {
+            name: name.clone(),
+            before: before.get(name).copied().unwrap_or(0),
+            after: after.get(name).copied().unwrap_or(0),
+        })
+        .filter(|delta| delta.before != delta.after)
+        .collect();
+    
+    // Largest change first, growth or shrinkage
+    deltas.sort_by(|x, y| {
+        x.after
+            .abs_diff(x.before)
+            .cmp(&y.after.abs_diff(y.before))
+            .reverse()
+            .then_with(|| x.name.cmp(&y.name))
+    });
+    deltas
+}
+
+// Compare two snapshots of a tree by relative path, matching moves by content
+fn compare_snapshots(a: &Snapshot, b: &Snapshot, depth: usize) -> SnapshotComparison {
+    let a_by_path: HashMap<&Path, &SnapshotFile> = a.files.iter().map(|f| (f.path.as_path(), f)).collect();
+    let b_by_path: HashMap<&Path, &SnapshotFile> = b.files.iter().map(|f| (f.path.as_path(), f)).collect();
+    
+    let mut removed: Vec<&SnapshotFile> = a.files.iter().filter(|f| !b_by_path.contains_key(f.path.as_path())).collect();
+    let mut added: Vec<&SnapshotFile> = b.files.iter().filter(|f| !a_by_path.contains_key(f.path.as_path())).collect();
+    removed.sort_by(|x, y| x.path.cmp(&y.path));
+    added.sort_by(|x, y| x.path.cmp(&y.path));
+    
+    // Without a hash, fall back to the same name, size and modification time
+    let move_key = |file: &SnapshotFile| match &file.hash {
+        Some(hash) => format!("hash:{}", hash),
+        None => format!(
+            "meta:{}:{}:{}",
+            file.path.file_name().unwrap_or_default().to_string_lossy(),
+            file.size,
+            file.last_modified
+        ),
+    };
+    
+    let mut added_by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, file) in added.iter().enumerate() {
+        added_by_key.entry(move_key(file)).or_default().push(index);
+    }
+    
+    // Each added file can be the destination of only one move
+    let mut moved = Vec::new();
+    let mut moved_to = HashSet::new();
+    removed.retain(|file| match added_by_key.get_mut(&move_key(file)).and_then(|indices| indices.pop()) {
+        Some(index) => {
+            moved.push(SnapshotMove {
+                from: file.path.clone(),
+                to: added[index].path.clone(),
+                size: file.size,
+            });
+            moved_to.insert(index);
+            false
+        }
+        None => true,
+    });
+    
+    let mut added: Vec<SnapshotFile> = added
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !moved_to.contains(index))
+        .map(|(_, file)| file.clone())
+        .collect();
+    let mut removed: Vec<SnapshotFile> = removed.into_iter().cloned().collect();
+    added.sort_by(|x, y| y.size.cmp(&x.size).then_with(|| x.path.cmp(&y.path)));
+    removed.sort_by(|x, y| y.size.cmp(&x.size).then_with(|| x.path.cmp(&y.path)));
+    
+    let mut grown: Vec<SnapshotChange> = a
+        .files
+        .iter()
+        .filter_map(|file| {
+            let newer = b_by_path.get(file.path.as_path())?;
+            (newer.size > file.size).then(|| SnapshotChange {
+                path: file.path.clone(),
+                before: file.size,
+                after: newer.size,
+            })
+        })
+        .collect();
+    grown.sort_by(|x, y| (y.after - y.before).cmp(&(x.after - x.before)).then_with(|| x.path.cmp(&y.path)));
+    
+    let totals = |snapshot: &Snapshot, key: &dyn Fn(&SnapshotFile) -> String| {
+        let mut totals: HashMap<String, u64> = HashMap::new();
+        for file in &snapshot.files {
+            *totals.entry(key(file)).or_insert(0) += file.size;
+        }
+        totals
+    };
+    let by_category = |file: &SnapshotFile| file.category.clone();
+    let by_directory = |file: &SnapshotFile| snapshot_directory(&file.path, depth);
+    
+    SnapshotComparison {
+        a_root: a.root.clone(),
+        a_created: a.created,
+        b_root: b.root.clone(),
+        b_created: b.created,
+        total_before: a.files.iter().map(|f| f.size).sum(),
+        total_after: b.files.iter().map(|f| f.size).sum(),
+        added,
+        removed,
+        grown,
+        moved,
+        categories: size_deltas(totals(a, &by_category), totals(b, &by_category)),
+        directories: size_deltas(totals(a, &by_directory), totals(b, &by_directory)),
+    }
+}
+
+// Signed, human-readable difference between two sizes
+fn format_size_delta(before: u64, after: u64) -> String {
+    if after >= before {
+        format!("+{}", format_size(after - before))
+    } else {
+        format!("-{}", format_size(before - after))
+    }
+}
+
+// Format a unix timestamp as a local date and time
+fn format_timestamp(timestamp: u64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp as i64, 0)
+        .map(|d| d.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+// Display what changed between two snapshots, `top` entries per list
+fn display_snapshot_comparison(comparison: &SnapshotComparison, top: usize) {
+    println!("\n{}", "Snapshot Comparison".bold().underline());
+    println!("A: {} ({})", comparison.a_root.display(), format_timestamp(comparison.a_created));
+    println!("B: {} ({})", comparison.b_root.display(), format_timestamp(comparison.b_created));
+    println!(
+        "\nTotal size: {} -> {} ({})",
+        format_size(comparison.total_before),
+        format_size(comparison.total_after),
+        format_size_delta(comparison.total_before, comparison.total_after).bold()
+    );
+    
+    let more = |shown: usize, total: usize| {
+        if total > shown {
+            println!("  ... and {} more", total - shown);
+        }
+    };
+    
+    let file_lists = [("Added", &comparison.added, "+"), ("Removed", &comparison.removed, "-")];
+    for (title, files, sign) in file_lists {
+        let size: u64 = files.iter().map(|f| f.size).sum();
+        println!("\n{} ({} files, {}{}):", title.bold().yellow(), files.len(), sign, format_size(size));
+        for file in files.iter().take(top) {
+            println!("  {:>12}  {:<22} {}", format_size(file.size).yellow(), file.category, file.path.display().to_string().cyan());
+        }
+        more(top, files.len());
+    }
+    
+    println!("\n{} ({} files):", "Grown".bold().yellow(), comparison.grown.len());
+    for change in comparison.grown.iter().take(top) {
+        println!(
+            "  {:>12}  {} -> {}  {}",
+            format_size_delta(change.before, change.after).yellow(),
+            format_size(change.before),
+            format_size(change.after),
+            change.path.display().to_string().cyan()
+        );
+    }
+    more(top, comparison.grown.len());
+    
+    println!("\n{} ({} files):", "Moved".bold().yellow(), comparison.moved.len());
+    for moved in comparison.moved.iter().take(top) {
+        println!("  {} -> {}", moved.from.display(), moved.to.display());
+    }
+    more(top, comparison.moved.len());
+    
+    let delta_lists = [("Size Change by Category", &comparison.categories), ("Size Change by Directory", &comparison.directories)];
+    for (title, deltas) in delta_lists {
+        println!("\n{}", title.bold().underline());
+        if deltas.is_empty() {
+            println!("  No changes");
+        }
+        for delta in deltas.iter().take(top) {
+            let change = format_size_delta(delta.before, delta.after);
+            let change = if delta.after > delta.before { change.red() } else { change.green() };
+            println!(
+                "  {:>12}  {:<30} {} -> {}",
+                change,
+                delta.name,
+                format_size(delta.before),
+                format_size(delta.after)
+            );
+        }
+        more(top, deltas.len());
+    }
+}
+
+// Format size in human-readable form
+fn format_size(size: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
+    
+    if size >= GB {
+        format!("{:.2} GB", size as f64 / GB as f64)
+    } else if size >= MB {
+        format!("{:.2} MB", size as f64 / MB as f64)
+    } else if size >= KB {
+        format!("{:.2} KB", size as f64 / KB as f64)
+    } else {
+        format!("{} bytes", size)
+    }
+}
+
+// Local "YYYY-MM" folder for a unix timestamp, or None if chrono can't represent it
+fn month_folder(timestamp: u64) -> Option<String> {
+    let datetime = DateTime::<Utc>::from_timestamp(i64::try_from(timestamp).ok()?, 0)?;
+    Some(datetime.with_timezone(&Local).format("%Y-%m").to_string())
+}
+
+// Month folder from an embedded date, falling back to mtime, along with where the date came from
+fn dated_folder(embedded: Option<u64>, source: &'static str, mtime: u64) -> (String, &'static str) {
+    match embedded.and_then(month_folder) {
+        Some(folder) => (folder, source),
+        None => (month_folder(mtime).unwrap_or_else(|| "unknown_date".to_string()), "mtime"),
+    }
+}
+
+// Metadata an organize method needs; templates may use tags of any kind
+fn organize_metadata(method: &str, template: bool) -> MetadataKinds {
+    if template {
+        return MetadataKinds::ALL;
+    }
+    
+    MetadataKinds {
+        exif: method == "exif-date",
+        video: method == "date",
+        document: method == "doc-date",
+        ..MetadataKinds::default()
+    }
+}
+
+// Organize files by moving them to category folders
+fn organize_files(
+    files: &[FileInfo],
+    target_dir: &Path,
+    organization_type: &str,
+    template: Option<&str>,
+    nested: bool,
+    dry_run: bool,
+    config: &TidyConfig,
+) -> Result<(), Box<dyn Error>> {
+    let pb = ProgressBar::new(files.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("##-"),
+    );
+    
+    let move_count = Arc::new(Mutex::new(0));
+    let error_count = Arc::new(Mutex::new(0));
+    let mut missing_tags = Vec::new();
+    for file in files {
+        // A template names the file as well as its folder
+        let rendered = match template {
+            Some(template) if !matches!(file.category, FileCategory::Project) => match render_template(template, file) {
+                Ok(rendered) => Some(rendered),
+                Err(missing) => {
+                    missing_tags.push((&file.path, missing));
+                    pb.inc(1);
+                    continue;
+                }
+            },
+            _ => None,
+        };
+        
+        // Where the date behind a date-based folder came from, shown in dry runs
+        let mut date_source = None;
+        let target_subdir = match organization_type {
+            _ if rendered.is_some() => rendered
+                .as_ref()
+                .and_then(|r| r.parent())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            // Projects move intact, to a rule's destination or the Projects folder
+            _ if matches!(file.category, FileCategory::Project) => {
+                let rule = config.project_rules.iter().find(|rule| {
+                    file.subcategory.as_deref() == Some(project_kind(&rule.marker).as_str())
+                });
+                
+                match rule {
+                    Some(rule) => expand_home(&rule.destination),
+                    None => category_index()
+                        .folder(&FileCategory::Project, None)
+                        .unwrap_or_else(|| "Projects".to_string()),
+                }
+            },
+            "type" => {
+                let subcategory = if nested { file.subcategory.as_deref() } else { None };
+                
+                match category_index().folder(&file.category, subcategory) {
+                    Some(folder) => folder,
+                    None => match &file.category {
+                        FileCategory::Other(ext) if ext == "unknown" => "Other".to_string(),
+                        _ => "Miscellaneous".to_string(),
+                    },
+                }
+            },
+            "date" => {
+                // Videos carry a creation time that survives copying, unlike mtime
+                let created = file.video.as_ref().and_then(|video| video.created);
+                let (folder, source) = dated_folder(created, "container", file.last_modified);
+                date_source = Some(source);
+                folder
+            },
+            "doc-date" => {
+                let created = file.document.as_ref().and_then(|doc| doc.created);
+                let (folder, source) = dated_folder(created, "document", file.last_modified);
+                date_source = Some(source);
+                folder
+            },
+            "exif-date" => {
+                let taken = file.exif.as_ref().and_then(|exif| exif.date_taken);
+                let (folder, source) = dated_folder(taken, "EXIF", file.last_modified);
+                date_source = Some(source);
+                folder
+            },
+            // Straight into the target, as when moving a hand-picked set
+            "flat" => String::new(),
+            "ext" => {
+                if let Some(extension) = split_extension(&file.path).1 {
+                    extension
+                } else {
+                    "no_extension".to_string()
+                }
+            },
+            _ => "Unsorted".to_string(),
+        };
+        
+        // Create a copy for later use
+        let subdir_name = target_subdir.clone();
+        let target_path = target_dir.join(target_subdir);
+        let file_name = match rendered.as_ref().and_then(|r| r.file_name()) {
+            Some(name) => name,
+            None => file.path.file_name().unwrap(),
+        };
+        
+        if dry_run {
+            let source = date_source.map(|source| format!(" ({})", source)).unwrap_or_default();
+            pb.suspend(|| println!("{} -> {}{}", file.path.display(), target_path.join(file_name).display(), source));
+        } else {
+            fs::create_dir_all(&target_path)?;
+            
+            let destination = target_path.join(file_name);
+            
+            if destination.exists() {
+                // Handle name collision by adding a timestamp
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                
+                let (file_stem, extension) = if matches!(file.category, FileCategory::Project) {
+                    (file_name.to_string_lossy().to_string(), None)
+                } else {
+                    split_extension(Path::new(file_name))
+                };
+                let extension = extension
+                    .map(|ext| format!(".{}", ext))
+                    .unwrap_or_default();
+                
+                let new_name = format!("{}_{}{}", file_stem, now, extension);
+                let destination = target_path.join(new_name);
+                
+                match fs::rename(&file.path, &destination) {
+                    Ok(_) => {
+                        let mut count = move_count.lock().unwrap();
+                        *count += 1;
+                    }
+                    Err(_) => {
+                        let mut errors = error_count.lock().unwrap();
+                        *errors += 1;
+                    }
+                }
+            } else {
+                match fs::rename(&file.path, &destination) {
+                    Ok(_) => {
+                        let mut count = move_count.lock().unwrap();
+                        *count += 1;
+                    }
+                    Err(_) => {
+                        let mut errors = error_count.lock().unwrap();
+                        *errors += 1;
+                    }
+                }
+            }
+        }
+          pb.inc(1);
+        pb.set_message(format!("Moving to {}", subdir_name));
+    }
+    
+    let move_count = *move_count.lock().unwrap();
+    let error_count = *error_count.lock().unwrap();
+    
+    if dry_run {
+        pb.finish_with_message("Dry run complete. No files were moved.");
+    } else {
+        pb.finish_with_message(format!(
+            "Organization complete. Moved {} files with {} errors",
+            move_count, error_count
+        ));
+    }
+    
+    if !missing_tags.is_empty() {
+        println!("\n{}", format!("{} files left in place because of missing tags:", missing_tags.len()).yellow().bold());
+        for (path, missing) in &missing_tags {
+            println!("  {} (missing {})", path.display(), missing.join(", "));
+        }
+    }
+    
+    Ok(())
+}
+
+// Directory holding journals of file operations
+fn journal_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let journal_dir = dirs::config_dir()
+        .ok_or("Could not determine config directory")?
+        .join("tidyfs")
+        .join("journal");
+    
+    fs::create_dir_all(&journal_dir)?;
+    
+    Ok(journal_dir)
+}
+
+// Write a journal of completed renames and return its path
+fn save_journal(operation: &str, entries: Vec<JournalEntry>) -> Result<PathBuf, Box<dyn Error>> {
+    let created = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let journal = Journal {
+        operation: operation.to_string(),
+        created,
+        entries,
+    };
+    
+    let journal_json = serde_json::to_string_pretty(&journal)?;
+    
+    let (journal_path, mut file) = create_unique_file(&journal_dir()?, &format!("{}-{}", operation, created), "json")?;
+    file.write_all(journal_json.as_bytes())?;
+    
+    Ok(journal_path)
+}
+
+// Create `{stem}.{extension}` in `dir`, adding -2, -3, ... when the name is taken,
+// so runs within the same second don't overwrite each other
+fn create_unique_file(dir: &Path, stem: &str, extension: &str) -> Result<(PathBuf, File), Box<dyn Error>> {
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut attempt = 1;
+    
+    loop {
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                attempt += 1;
+                path = dir.join(format!("{}-{}.{}", stem, attempt, extension));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// Get the snapshot directory, creating it if needed
+fn snapshot_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let snapshot_dir = dirs::config_dir()
+        .ok_or("Could not determine config directory")?
+        .join("tidyfs")
+        .join("snapshots");
+    
+    fs::create_dir_all(&snapshot_dir)?;
+    
+    Ok(snapshot_dir)
+}
+
+// Write a gzip-compressed snapshot named after its timestamp and root, and return its path
+fn save_snapshot(snapshot: &Snapshot) -> Result<PathBuf, Box<dyn Error>> {
+    let root_name = snapshot
+        .root
+        .file_name()
+        .and_then(|name| sanitize_component(&name.to_string_lossy()))
+        .unwrap_or_else(|| "root".to_string());
+    let (snapshot_path, file) = create_unique_file(&snapshot_dir()?, &format!("{}-{}", snapshot.created, root_name), "json.gz")?;
+    
+    let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    serde_json::to_writer(&mut encoder, snapshot)?;
+    encoder.finish()?;
+    
+    Ok(snapshot_path)
+}
+
+// Load a snapshot from a path, or by file name from the snapshot directory
+fn load_snapshot(name: &str) -> Result<Snapshot, Box<dyn Error>> {
+    let path = Path::new(name);
+    let path = if path.exists() { path.to_path_buf() } else { snapshot_dir()?.join(name) };
+    
+    let file = File::open(&path).map_err(|e| format!("Could not open snapshot {}: {}", path.display(), e))?;
+    let snapshot: Snapshot = serde_json::from_reader(std::io::BufReader::new(flate2::read::GzDecoder::new(file)))
+        .map_err(|e| format!("Could not read snapshot {}: {}", path.display(), e))?;
+    
+    // Older versions are read through serde aliases; newer ones may mean something else
+    if snapshot.schema_version == 0 || snapshot.schema_version > SNAPSHOT_SCHEMA_VERSION {
+        return Err(format!(
+            "Snapshot {} has schema version {}, but this tidyfs reads versions 1 to {}",
+            path.display(),
+            snapshot.schema_version,
+            SNAPSHOT_SCHEMA_VERSION
+        )
+        .into());
+    }
+    
+    Ok(snapshot)
+}
+
+// List saved snapshots, oldest first
+fn list_snapshots() -> Result<(), Box<dyn Error>> {
+    let mut names: Vec<String> = fs::read_dir(snapshot_dir()?)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".json.gz"))
+        .collect();
+    names.sort();
+    
+    if names.is_empty() {
+        println!("No snapshots saved yet (use scan --save-snapshot).");
+        return Ok(());
+    }
+    
+    println!("{}", "Saved Snapshots:".bold().underline());
+    for name in names {
+        match load_snapshot(&name) {
+            Ok(snapshot) => println!(
+                "  {}  {}  {} files, {}  {}",
+                name.cyan(),
+                format_timestamp(snapshot.created),
+                snapshot.files.len(),
+                format_size(snapshot.files.iter().map(|f| f.size).sum()),
+                snapshot.root.display()
+            ),
+            Err(e) => println!("  {}  {}", name.cyan(), e.to_string().red()),
+        }
+    }
+    
+    Ok(())
+}
+
+// Reverse the renames recorded in a journal, newest first
+fn undo_journal(journal_path: &Path) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(journal_path)?;
+    let journal: Journal = serde_json::from_str(&contents)?;
+    
+    let mut restored = 0;
+    let mut skipped = 0;
+    
+    for entry in journal.entries.iter().rev() {
+        if !entry.to.exists() || entry.from.exists() {
+            println!("  {} {}", "skip".yellow(), entry.to.display());
+            skipped += 1;
+            continue;
+        }
+        
+        match fs::rename(&entry.to, &entry.from) {
+            Ok(_) => restored += 1,
+            Err(e) => {
+                println!("  {} {} ({})", "error".red(), entry.to.display(), e);
+                skipped += 1;
+            }
+        }
+    }
+    
+    println!(
+        "Undid '{}': restored {} files, skipped {}",
+        journal.operation, restored, skipped
+    );
+    
+    Ok(())
+}
+
+// Rename files so their extension matches their content
+fn fix_extensions(mismatches: &[ExtensionMismatch], dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let mut entries = Vec::new();
+    let mut error_count = 0;
+    
+    for mismatch in mismatches {
+        let (stem, _) = split_extension(&mismatch.path);
+        
+        let destination = mismatch
+            .path
+            .with_file_name(format!("{}.{}", stem, mismatch.detected_extension));
+        
+        println!(
+            "  {} -> {}",
+            mismatch.path.display(),
+            destination.file_name().unwrap().to_string_lossy().cyan()
+        );
+        
+        if dry_run {
+            continue;
+        }
+        
+        if destination.exists() {
+            println!("    {} destination already exists", "skip".yellow());
+            error_count += 1;
+            continue;
+        }
+        
+        match fs::rename(&mismatch.path, &destination) {
+            Ok(_) => entries.push(JournalEntry {
+                from: mismatch.path.clone(),
+                to: destination,
+            }),
+            Err(e) => {
+                println!("    {} {}", "error".red(), e);
+                error_count += 1;
+            }
+        }
+    }
+    
+    if dry_run {
+        println!("\nDry run complete. No files were renamed.");
+        return Ok(());
+    }
+    
+    let renamed = entries.len();
+    let journal_path = save_journal("fix-extensions", entries)?;
+    
+    println!(
+        "\nRenamed {} files with {} errors. Journal: {}",
+        renamed,
+        error_count,
+        journal_path.display()
+    );
+    
+    Ok(())
+}
+
+// Load config from file or create default
+fn load_config() -> Result<TidyConfig, Box<dyn Error>> {
+    let config_dir = dirs::config_dir()
+        .ok_or("Could not determine config directory")?
+        .join("tidyfs");
+    
+    fs::create_dir_all(&config_dir)?;
+    
+    let config_path = config_dir.join("config.json");
+    
+    if config_path.exists() {
+        let mut file = File::open(config_path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        
+        let config: TidyConfig = serde_json::from_str(&contents)?;
+        Ok(config)
+    } else {
+        let config = TidyConfig::default();
+        save_config(&config)?;
+        Ok(config)
+    }
+}
+
+// Save config to file
+fn save_config(config: &TidyConfig) -> Result<(), Box<dyn Error>> {
+    let config_dir = dirs::config_dir()
+        .ok_or("Could not determine config directory")?
+        .join("tidyfs");
+    
+    fs::create_dir_all(&config_dir)?;
+    
+    let config_path = config_dir.join("config.json");
+    let config_json = serde_json::to_string_pretty(config)?;
+    
+    let mut file = File::create(config_path)?;
+    file.write_all(config_json.as_bytes())?;
+    
+    Ok(())
+}
+
+// Update config with a new recently used directory
+fn update_recent_directories(config: &mut TidyConfig, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let dir_path = dir.to_path_buf();
+    
+    // Remove if already exists
+    config.recent_directories.retain(|path| path != &dir_path);
+    
+    // Add to front
+    config.recent_directories.insert(0, dir_path);
+    
+    // Keep only the 10 most recent
+    if config.recent_directories.len() > 10 {
+        config.recent_directories.truncate(10);
+    }
+    
+    save_config(config)
+}
+
+// Report label for a file's category, rolled up to the top level or split by subcategory
+fn category_label(file: &FileInfo, by_subcategory: bool) -> String {
+    let subcategory = if by_subcategory { file.subcategory.as_deref() } else { None };
+    
+    match category_index().folder(&file.category, subcategory) {
+        Some(folder) => folder,
+        None => match &file.category {
+            FileCategory::Other(ext) if ext == "unknown" => "Unknown".to_string(),
+            FileCategory::Other(ext) => format!("Other (.{})", ext),
+            category => category.name().to_string(),
+        },
+    }
+}
+
+// Display total video running time and a resolution breakdown
+fn display_video_summary(files: &[FileInfo]) {
+    // Nothing to show unless metadata was read
+    if !files.iter().any(|f| f.video.is_some()) {
+        return;
+    }
+    
+    let videos: Vec<Option<&VideoInfo>> = files
+        .iter()
+        .filter(|f| matches!(f.category, FileCategory::Video))
+        .map(|f| f.video.as_ref())
+        .collect();
+    let total_secs: f64 = videos.iter().flatten().filter_map(|v| v.duration_secs).sum();
+    
+    // Classify by the longer side so portrait videos land in the same class
+    let mut classes: Vec<(&str, usize)> = vec![("4K+", 0), ("HD", 0), ("SD", 0), ("Unknown", 0)];
+    for video in &videos {
+        let index = match video.map_or((None, None), |v| (v.width, v.height)) {
+            (Some(w), Some(h)) if w.max(h) >= 3840 => 0,
+            (Some(w), Some(h)) if w.max(h) >= 1280 => 1,
+            (Some(_), Some(_)) => 2,
+            _ => 3,
+        };
+        classes[index].1 += 1;
+    }
+    
+    println!("\n{}", "Video Summary".bold().underline());
+    println!(
+        "{} videos, {:.1} hours total",
+        videos.len(),
+        total_secs / 3600.0
+    );
+    
+    for (class, count) in classes.into_iter().filter(|(_, count)| *count > 0) {
+        println!("  {:<10} {}", class, count);
+    }
+}
+
+// Flatten a file into a structured output record
+fn file_record(file: &FileInfo) -> FileRecord {
+    FileRecord {
+        path: file.path.clone(),
+        size: file.size,
+        last_modified: file.last_modified,
+        // Same names as the report's category totals, so the two can be joined
+        category: category_label(file, false),
+        subcategory: file.subcategory.clone(),
+        hash: file.hash.clone(),
+        exif: file.exif.clone(),
+        audio: file.audio.clone(),
+        video: file.video.clone(),
+        document: file.document.clone(),
+    }
+}
+
+// Aggregate category totals, largest files and duplicate groups
+fn build_storage_report(
+    root: &Path,
+    files: &[FileInfo],
+    options: &ReportOptions,
+    duplicates: Option<&DuplicateGroups<'_>>,
+) -> StorageReport {
+    let total_size: u64 = files.iter().map(|f| f.size).sum();
+    
+    // Group by category
+    let mut category_sizes: HashMap<String, u64> = HashMap::new();
+    let mut category_counts: HashMap<String, usize> = HashMap::new();
+    
+    for file in files {
+        let category = category_label(file, options.by_subcategory);
+        
+        *category_sizes.entry(category.clone()).or_insert(0) += file.size;
+        *category_counts.entry(category).or_insert(0) += 1;
+    }
+    
+    // Sort categories by size descending, then name so output is stable
+    let mut categories: Vec<CategoryTotal> = category_sizes
+        .iter()
+        .map(|(category, &size)| CategoryTotal {
+            category: category.clone(),
+            size,
+            files: *category_counts.get(category).unwrap_or(&0),
+            percent: if total_size > 0 { size as f64 / total_size as f64 * 100.0 } else { 0.0 },
+        })
+        .collect();
+    
+    categories.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.category.cmp(&b.category)));
+    
+    let largest_files = largest_records(files.iter(), options.top);
+    
+    // Per-category lists follow the category order above
+    let category_top_files = categories
+        .iter()
+        .map(|total| CategoryTopFiles {
+            category: total.category.clone(),
+            files: largest_records(
+                files
+                    .iter()
+                    .filter(|f| category_label(f, options.by_subcategory) == total.category),
+                options.top,
+            ),
+        })
+        .collect();
+    
+    let largest_directories = largest_directories(root, &build_directory_tree(root, files), options.top);
+    
+    // Per-category ages follow the category order above
+    let category_ages = categories
+        .iter()
+        .map(|total| CategoryAges {
+            category: total.category.clone(),
+            buckets: build_age_buckets(
+                files
+                    .iter()
+                    .filter(|f| category_label(f, options.by_subcategory) == total.category),
+            ),
+        })
+        .collect();
+    
+    let stale_before = stale_cutoff(options.stale_years);
+    let stale_files = largest_records(files.iter().filter(|f| f.last_modified < stale_before), options.stale_top);
+    
+    StorageReport {
+        total_files: files.len(),
+        total_size,
+        categories,
+        largest_files,
+        largest_directories,
+        category_top_files,
+        duplicate_groups: duplicates.map(build_duplicate_report).unwrap_or_default(),
+        age_buckets: build_age_buckets(files),
+        category_ages,
+        stale_years: options.stale_years,
+        stale_files,
+        skipped_duplicates: Vec::new(),
+    }
+}
+
+// The `top` largest files as output records, largest first
+fn largest_records<'a>(files: impl Iterator<Item = &'a FileInfo>, top: usize) -> Vec<FileRecord> {
+    let mut files: Vec<&FileInfo> = files.collect();
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    files.into_iter().take(top).map(file_record).collect()
+}
+
+// The `top` largest directories directly below `root`; nested ones are left to --tree,
+// so a parent and its own subdirectories don't fill the list together
+fn largest_directories(root: &Path, tree: &DirectoryNode, top: usize) -> Vec<DirectoryTotal> {
+    let mut directories: Vec<DirectoryTotal> = tree
+        .children
+        .iter()
+        .map(|(name, child)| DirectoryTotal {
+            path: root.join(name),
+            size: child.size,
+            files: child.files,
+        })
+        .collect();
+    
+    directories.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    directories.truncate(top);
+    directories
+}
+
+// Current time as a unix timestamp
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Files last modified before this timestamp count as stale
+fn stale_cutoff(stale_years: u64) -> u64 {
+    unix_now().saturating_sub(stale_years.saturating_mul(365 * 86_400))
+}
+
+// Group files by how long ago they were last modified
+fn build_age_buckets<'a>(files: impl IntoIterator<Item = &'a FileInfo>) -> Vec<AgeBucket> {
+    let now = unix_now();
+    
+    let mut buckets: Vec<AgeBucket> = AGE_BUCKETS
+        .iter()
+        .map(|&(label, max_age_days)| AgeBucket {
+            label: label.to_string(),
+            max_age_days,
+            size: 0,
+            files: 0,
+        })
+        .collect();
+    
+    for file in files {
+        let age_days = now.saturating_sub(file.last_modified) / 86_400;
+        let index = AGE_BUCKETS
+            .iter()
+            .position(|&(_, max)| max.is_none_or(|max| age_days < max))
+            .unwrap_or(AGE_BUCKETS.len() - 1);
+        
+        buckets[index].size += file.size;
+        buckets[index].files += 1;
+    }
+    
+    buckets
+}
+
+// Sum file sizes into a tree of directories below `root`
+fn build_directory_tree(root: &Path, files: &[FileInfo]) -> DirectoryNode {
+    let mut tree = DirectoryNode::default();
+    
+    for file in files {
+        let relative = file.path.strip_prefix(root).unwrap_or(&file.path);
+        let mut node = &mut tree;
+        node.size += file.size;
+        node.files += 1;
+        
+        // Every component but the last is a directory
+        if let Some(parent) = relative.parent() {
+            for component in parent.components() {
+                node = node
+                    .children
+                    .entry(component.as_os_str().to_string_lossy().to_string())
+                    .or_default();
+                node.size += file.size;
+                node.files += 1;
+            }
+        }
+    }
+    
+    tree
+}
+
+// Drop subdirectories smaller than `min_size`; their bytes stay counted in the parent
+fn prune_directory_tree(node: &mut DirectoryNode, min_size: u64) {
+    node.children.retain(|_, child| child.size >= min_size);
+    for child in node.children.values_mut() {
+        prune_directory_tree(child, min_size);
+    }
+}
+
+// Write a self-contained HTML report with charts built from the storage report
+fn write_html_report(path: &Path, root: &Path, files: &[FileInfo], report: &StorageReport) -> Result<(), Box<dyn Error>> {
+    let mut tree = build_directory_tree(root, files);
+    // Keep the embedded tree small enough for a browser on large scans
+    prune_directory_tree(&mut tree, report.total_size / 2000);
+    
+    let data = serde_json::json!({
+        "root": root,
+        "generated": Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        "report": report,
+        "tree": tree,
+    });
+    
+    // "</" inside a script element would end it early
+    let data = serde_json::to_string(&data)?.replace("</", "<\\/");
+    let html = HTML_REPORT_TEMPLATE.replace("/*TIDYFS_DATA*/null", &data);
+    
+    fs::write(path, html)?;
+    Ok(())
+}
+
+// Escape a Prometheus label value
+fn prometheus_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Write scan results as Prometheus gauges for node_exporter's textfile collector
+fn write_prometheus_metrics(
+    path: &Path,
+    root: &Path,
+    files: &[FileInfo],
+    report: &StorageReport,
+    stats: &ScanRunStats,
+) -> Result<(), Box<dyn Error>> {
+    // Label with an absolute root so relative invocations land on the same series
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let root_label = format!("root=\"{}\"", prometheus_label(&root.to_string_lossy()));
+    
+    let mut categories: BTreeMap<String, (u64, usize)> = BTreeMap::new();
+    for file in files {
+        let total = categories.entry(category_label(file, false)).or_insert((0, 0));
+        total.0 += file.size;
+        total.1 += 1;
+    }
+    let category_labels = |category: &str| format!("{},category=\"{}\"", root_label, prometheus_label(category));
+    
+    let stale_before = stale_cutoff(report.stale_years);
+    let stale: Vec<&FileInfo> = files.iter().filter(|f| f.last_modified < stale_before).collect();
+    
+    let mut metrics = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, String)>| {
+        metrics.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name));
+        for (labels, value) in samples {
+            metrics.push_str(&format!("{}{{{}}} {}\n", name, labels, value));
+        }
+    };
+    
+    gauge("tidyfs_bytes", "Total size of the scanned files in bytes.", vec![(root_label.clone(), report.total_size.to_string())]);
+    gauge("tidyfs_files", "Number of scanned files.", vec![(root_label.clone(), report.total_files.to_string())]);
+    gauge(
+        "tidyfs_category_bytes",
+        "Size of the scanned files per category in bytes.",
+        categories.iter().map(|(category, (size, _))| (category_labels(category), size.to_string())).collect(),
+    );
+    gauge(
+        "tidyfs_category_files",
+        "Number of scanned files per category.",
+        categories.iter().map(|(category, (_, count))| (category_labels(category), count.to_string())).collect(),
+    );
+    
+    // Without hashing there is no duplicate data, rather than zero duplicates
+    if stats.duplicates_checked {
+        let wasted: u64 = report.duplicate_groups.iter().map(|group| group.wasted_bytes).sum();
+        gauge("tidyfs_duplicate_wasted_bytes", "Bytes taken by duplicate copies beyond the first.", vec![(root_label.clone(), wasted.to_string())]);
+        gauge("tidyfs_duplicate_groups", "Number of groups of identical files.", vec![(root_label.clone(), report.duplicate_groups.len().to_string())]);
+    }
+    
+    let stale_help = |what: &str| format!("{} files not modified for more than {} years.", what, report.stale_years);
+    gauge(
+        "tidyfs_stale_bytes",
+        &stale_help("Size in bytes of"),
+        vec![(root_label.clone(), stale.iter().map(|f| f.size).sum::<u64>().to_string())],
+    );
+    gauge("tidyfs_stale_files", &stale_help("Number of"), vec![(root_label.clone(), stale.len().to_string())]);
+    gauge("tidyfs_scan_duration_seconds", "Time taken by the scan in seconds.", vec![(root_label.clone(), format!("{:.3}", stats.duration_secs))]);
+    gauge("tidyfs_scan_errors", "Number of entries that could not be read.", vec![(root_label.clone(), stats.errors.to_string())]);
+    gauge("tidyfs_last_scan_timestamp_seconds", "Unix time the scan finished.", vec![(root_label, unix_now().to_string())]);
+    
+    // Write beside the target and rename, so the collector never reads a partial file
+    let file_name = path.file_name().ok_or("Prometheus output needs a file name")?;
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+    let written = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(metrics.as_bytes())?;
+        file.sync_all()
+    });
+    
+    if let Err(e) = written.and_then(|_| fs::rename(&temp_path, path)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("Could not write {}: {}", path.display(), e).into());
+    }
+    
+    Ok(())
+}
+
+// Display storage usage report
+fn display_storage_report(report: &StorageReport, per_category: bool) {
+    println!("\n{}", "Storage Usage Report".bold().underline());
+    println!(
+        "Total: {} files, {}",
+        report.total_files,
+        format_size(report.total_size).bold()
+    );
+    
+    println!("\n{:<20} {:<15} {:<10} {:<10}", 
+             "Category".bold(), 
+             "Size".bold(), 
+             "Files".bold(), 
+             "% of Total".bold());
+    
+    println!("{}", "-".repeat(55));
+    
+    for category in &report.categories {
+        println!(
+            "{:<20} {:<15} {:<10} {:.1}%",
+            category.category,
+            format_size(category.size),
+            category.files,
+            category.percent
+        );
+    }
+    
+    println!("\n{}", "Largest Files:".bold().underline());
+    display_file_list(&report.largest_files);
+    
+    println!("\n{}", "Largest Directories:".bold().underline());
+    if report.largest_directories.is_empty() {
+        println!("None (no subdirectories scanned)");
+    }
+    for directory in &report.largest_directories {
+        println!(
+            "{:>12} {:>8} files  {}",
+            format_size(directory.size).yellow(),
+            directory.files,
+            directory.path.display().to_string().cyan()
+        );
+    }
+    
+    if per_category {
+        for category in &report.category_top_files {
+            println!("\n{}", format!("Largest {}:", category.category).bold().underline());
+            display_file_list(&category.files);
+        }
+    }
+}
+
+// Display files with size, modification date and category columns
+fn display_file_list(files: &[FileRecord]) {
+    for file in files {
+        let modified = DateTime::<Utc>::from_timestamp(file.last_modified as i64, 0)
+            .map(|d| d.with_timezone(&Local).format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let category = match &file.subcategory {
+            Some(subcategory) => format!("{}/{}", file.category, subcategory),
+            None => file.category.clone(),
+        };
+        
+        println!(
+            "{:>12}  {}  {:<22} {}",
+            format_size(file.size).yellow(),
+            modified,
+            category,
+            file.path.display().to_string().cyan()
+        );
+    }
+}
+
+// Display size and count per age bucket, per category, and the largest stale files
+fn display_age_report(report: &StorageReport) {
+    println!("\n{}", "File Age".bold().underline());
+    println!("{:<14} {:>12} {:>8} {:>10}", "Modified".bold(), "Size".bold(), "Files".bold(), "% of Size".bold());
+    println!("{}", "-".repeat(47));
+    
+    for bucket in &report.age_buckets {
+        let percent = if report.total_size > 0 { bucket.size as f64 / report.total_size as f64 * 100.0 } else { 0.0 };
+        println!("{:<14} {:>12} {:>8} {:>9.1}%", bucket.label, format_size(bucket.size), bucket.files, percent);
+    }
+    
+    println!("\n{}", "Size by Category and Age".bold().underline());
+    print!("{:<20}", "Category".bold());
+    for bucket in &report.age_buckets {
+        print!(" {:>11}", bucket.label.bold());
+    }
+    println!();
+    println!("{}", "-".repeat(20 + 12 * report.age_buckets.len()));
+    
+    for category in &report.category_ages {
+        print!("{:<20}", category.category);
+        for bucket in &category.buckets {
+            let size = if bucket.files > 0 { format_size(bucket.size) } else { "-".to_string() };
+            print!(" {:>11}", size);
+        }
+        println!();
+    }
+    
+    println!(
+        "\n{}",
+        format!("Largest Files Untouched for {}+ Years:", report.stale_years).bold().underline()
+    );
+    if report.stale_files.is_empty() {
+        println!("None");
+    }
+    display_file_list(&report.stale_files);
+}
+
+// Display directory totals as an indented tree, largest first
+fn display_directory_tree(root: &Path, tree: &DirectoryNode, max_depth: usize, min_percent: f64) {
+    println!("\n{}", "Directory Tree".bold().underline());
+    println!(
+        "{:<48} {:>12} {:>7} {:>8}",
+        root.display().to_string().cyan(),
+        format_size(tree.size),
+        "100.0%",
+        tree.files
+    );
+    
+    let mut lines = Vec::new();
+    tree_child_lines(tree, "", 1, max_depth, tree.size, min_percent, &mut lines);
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+// Render one level of the directory tree, folding entries below the cut-off into one line
+fn tree_child_lines(
+    node: &DirectoryNode,
+    prefix: &str,
+    depth: usize,
+    max_depth: usize,
+    total: u64,
+    min_percent: f64,
+    out: &mut Vec<String>,
+) {
+    let percent = |size: u64| if total > 0 { size as f64 / total as f64 * 100.0 } else { 0.0 };
+    
+    // Files directly in this directory show up as one entry next to the subdirectories
+    let child_size: u64 = node.children.values().map(|c| c.size).sum();
+    let child_files: usize = node.children.values().map(|c| c.files).sum();
+    let own = (node.size - child_size, node.files - child_files);
+    
+    let mut entries: Vec<(String, Option<&DirectoryNode>, u64, usize)> = node
+        .children
+        .iter()
+        .map(|(name, child)| (format!("{}/", name), Some(child), child.size, child.files))
+        .collect();
+    if own.1 > 0 && !entries.is_empty() {
+        entries.push(("(files)".to_string(), None, own.0, own.1));
+    }
+    entries.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    
+    let (shown, hidden): (Vec<_>, Vec<_>) = entries.into_iter().partition(|e| percent(e.2) >= min_percent);
+    let hidden_size: u64 = hidden.iter().map(|e| e.2).sum();
+    let hidden_files: usize = hidden.iter().map(|e| e.3).sum();
+    let lines = shown.len() + usize::from(!hidden.is_empty());
+    
+    for (i, (name, child, size, files)) in shown.iter().enumerate() {
+        let last = i + 1 == lines;
+        let label = format!("{}{}{}", prefix, if last { "└── " } else { "├── " }, name);
+        out.push(format!("{:<48} {:>12} {:>6.1}% {:>8}", label, format_size(*size), percent(*size), files));
+        
+        if let Some(child) = child {
+            if depth < max_depth {
+                let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                tree_child_lines(child, &child_prefix, depth + 1, max_depth, total, min_percent, out);
+            }
+        }
+    }
+    
+    if !hidden.is_empty() {
+        let noun = if hidden.len() == 1 { "entry" } else { "entries" };
+        let label = format!("{}└── ({} smaller {})", prefix, hidden.len(), noun);
+        out.push(format!(
+            "{:<48} {:>12} {:>6.1}% {:>8}",
+            label.dimmed(),
+            format_size(hidden_size),
+            percent(hidden_size),
+            hidden_files
+        ));
+    }
+}
+
+// Write scan results to stdout as json, ndjson or csv
+fn write_scan_output(format: &str, root: &Path, files: &[FileInfo], report: &StorageReport) -> Result<(), Box<dyn Error>> {
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    
+    // Files arrive in scan order, which varies between runs
+    let mut file_records: Vec<FileRecord> = files.iter().map(file_record).collect();
+    file_records.sort_by(|a, b| a.path.cmp(&b.path));
+    
+    match format {
+        "json" => {
+            let output = ScanOutput {
+                schema_version: SCAN_SCHEMA_VERSION,
+                root,
+                files: file_records,
+                report,
+            };
+            serde_json::to_writer_pretty(&mut out, &output)?;
+            writeln!(out)?;
+        }
+        "ndjson" => {
+            let mut records = vec![ScanRecord::Summary {
+                schema_version: SCAN_SCHEMA_VERSION,
+                root,
+                total_files: report.total_files,
+                total_size: report.total_size,
+            }];
+            records.extend(file_records.iter().map(ScanRecord::File));
+            records.extend(report.categories.iter().map(ScanRecord::Category));
+            records.extend(report.largest_files.iter().map(ScanRecord::LargestFile));
+            records.extend(report.largest_directories.iter().map(ScanRecord::LargestDirectory));
+            records.extend(report.category_top_files.iter().flat_map(|top| {
+                top.files.iter().map(|file| ScanRecord::CategoryTopFile {
+                    list: top.category.as_str(),
+                    file,
+                })
+            }));
+            records.extend(report.duplicate_groups.iter().map(ScanRecord::DuplicateGroup));
+            records.extend(report.age_buckets.iter().map(|bucket| ScanRecord::AgeBucket { category: None, bucket }));
+            records.extend(report.category_ages.iter().flat_map(|ages| {
+                ages.buckets.iter().map(|bucket| ScanRecord::AgeBucket {
+                    category: Some(ages.category.as_str()),
+                    bucket,
+                })
+            }));
+            records.extend(report.stale_files.iter().map(ScanRecord::StaleFile));
+            records.extend(report.skipped_duplicates.iter().map(ScanRecord::SkippedDuplicate));
+            
+            for record in records {
+                serde_json::to_writer(&mut out, &record)?;
+                writeln!(out)?;
+            }
+        }
+        "csv" => {
+            // One table for everything; the record column tells the rows apart
+            writeln!(out, "record,path,category,subcategory,size,files,last_modified,hash,label")?;
+            writeln!(out, "total,{},,,{},{},,,", csv_escape(&root.to_string_lossy()), report.total_size, report.total_files)?;
+            
+            let mut write_file = |record: &str, file: &FileRecord, label: &str| {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},,{},{},{}",
+                    record,
+                    csv_escape(&file.path.to_string_lossy()),
+                    csv_escape(&file.category),
+                    csv_escape(file.subcategory.as_deref().unwrap_or("")),
+                    file.size,
+                    file.last_modified,
+                    file.hash.as_deref().unwrap_or(""),
+                    csv_escape(label)
+                )
+            };
+            
+            for file in &file_records {
+                write_file("file", file, "")?;
+            }
+            for file in &report.largest_files {
+                write_file("largest_file", file, "")?;
+            }
+            for top in &report.category_top_files {
+                for file in &top.files {
+                    write_file("category_top_file", file, &top.category)?;
+                }
+            }
+            for file in &report.stale_files {
+                write_file("stale_file", file, "")?;
+            }
+            
+            for directory in &report.largest_directories {
+                writeln!(
+                    out,
+                    "largest_directory,{},,,{},{},,,",
+                    csv_escape(&directory.path.to_string_lossy()),
+                    directory.size,
+                    directory.files
+                )?;
+            }
+            
+            for category in &report.categories {
+                writeln!(out, "category,,{},,{},{},,,", csv_escape(&category.category), category.size, category.files)?;
+            }
+            
+            let overall = std::iter::once(("", &report.age_buckets));
+            let per_category = report.category_ages.iter().map(|ages| (ages.category.as_str(), &ages.buckets));
+            for (category, buckets) in overall.chain(per_category) {
+                for bucket in buckets {
+                    writeln!(
+                        out,
+                        "age_bucket,,{},,{},{},,,{}",
+                        csv_escape(category),
+                        bucket.size,
+                        bucket.files,
+                        csv_escape(&bucket.label)
+                    )?;
+                }
+            }
+            
+            for group in &report.duplicate_groups {
+                for file in &group.files {
+                    writeln!(
+                        out,
+                        "duplicate,{},,,{},{},{},{},",
+                        csv_escape(&file.path.to_string_lossy()),
+                        group.size,
+                        group.files.len(),
+                        file.last_modified,
+                        group.hash
+                    )?;
+                }
+            }
+            
+            for skipped in &report.skipped_duplicates {
+                writeln!(
+                    out,
+                    "skipped_duplicate,{},,,,,,,{}",
+                    csv_escape(&skipped.path.to_string_lossy()),
+                    csv_escape(&skipped.reason)
+                )?;
+            }
+        }
+        other => return Err(format!("Unknown output format '{}' (use text, json, ndjson or csv)", other).into()),
+    }
+    
+    out.flush()?;
+    Ok(())
+}
+
+// Name shown for a check rule: its own name, or a summary of its filters
+fn check_rule_label(rule: &CheckRule) -> String {
+    if let Some(name) = &rule.name {
+        return name.clone();
+    }
+    
+    let mut scope = Vec::new();
+    if let Some(category) = &rule.category {
+        scope.push(category.clone());
+    }
+    if !rule.paths.is_empty() {
+        scope.push(format!("in {}", rule.paths.join(", ")));
+    }
+    
+    if scope.is_empty() { "all files".to_string() } else { scope.join(" ") }
+}
+
+// Whether a file belongs to a category given by type ("Video"), folder ("Videos") or subcategory
+fn check_category_matches(category: &str, file: &FileInfo) -> bool {
+    [
+        file.category.name().to_string(),
+        category_label(file, false),
+        category_label(file, true),
+    ]
+    .iter()
+    .any(|name| name.eq_ignore_ascii_case(category))
+}
+
+// Whether a check rule's category names anything a scan can produce
+fn known_check_category(category: &str, config: &TidyConfig) -> bool {
+    let custom = config
+        .category_rules
+        .iter()
+        .map(|rule| &rule.name)
+        .chain(config.custom_categories.keys())
+        .any(|name| name.eq_ignore_ascii_case(category));
+    
+    custom
+        || category_index().knows_name(category)
+        || category.eq_ignore_ascii_case("Unknown")
+        || (category.starts_with("Other (.") && category.ends_with(')'))
+        || !matches!(FileCategory::from_name(category), FileCategory::Other(_))
+}
+
+// Evaluate every limit of every rule against the scanned files
+fn evaluate_check_rules(root: &Path, files: &[FileInfo], rules: &[CheckRule]) -> Result<Vec<CheckResult>, Box<dyn Error>> {
+    let root = canonical_path(root);
+    let mut results = Vec::new();
+    
+    for rule in rules {
+        let label = check_rule_label(rule);
+        let scope = PathScope::from(
+            rule.paths
+                .iter()
+                .map(|pattern| root.join(expand_home(pattern)).to_string_lossy().to_string())
+                .collect::<Vec<String>>(),
+        );
+        
+        let mut matched: Vec<&FileInfo> = files
+            .iter()
+            .filter(|file| rule.category.as_deref().is_none_or(|category| check_category_matches(category, file)))
+            .filter(|file| scope.contains(&file.path))
+            .collect();
+        matched.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        
+        let total: u64 = matched.iter().map(|f| f.size).sum();
+        let mut result = |limit: String, passed: bool, message: String, files: &[&FileInfo]| {
+            results.push(CheckResult {
+                rule: label.clone(),
+                limit,
+                passed,
+                message,
+                files: if passed { Vec::new() } else { files.iter().map(|f| file_record(f)).collect() },
+            });
+        };
+        
+        if let Some(limit) = &rule.max_total_size {
+            let limit = parse_size(limit)?;
+            result(
+                format!("total size <= {}", format_size(limit)),
+                total <= limit,
+                format!("{} in {} files", format_size(total), matched.len()),
+                &matched[..matched.len().min(10)],
+            );
+        }
+        
+        if let Some(limit) = rule.max_files {
+            result(
+                format!("files <= {}", limit),
+                matched.len() <= limit,
+                format!("{} files", matched.len()),
+                &matched[..matched.len().min(10)],
+            );
+        }
+        
+        if let Some(limit) = &rule.max_file_size {
+            let limit = parse_size(limit)?;
+            let offenders: Vec<&FileInfo> = matched.iter().copied().filter(|f| f.size > limit).collect();
+            let message = match (offenders.len(), matched.first()) {
+                (0, Some(largest)) => format!("largest is {}", format_size(largest.size)),
+                (0, None) => "no files".to_string(),
+                (count, _) => format!("{} files over the limit", count),
+            };
+            result(format!("file size <= {}", format_size(limit)), offenders.is_empty(), message, &offenders);
+        }
+        
+        if let Some(age) = &rule.max_age {
+            let cutoff = unix_now().saturating_sub(parse_age(age)?.saturating_mul(86_400));
+            let offenders: Vec<&FileInfo> = matched.iter().copied().filter(|f| f.last_modified < cutoff).collect();
+            let message = format!(
+                "{} files ({}) older than {}",
+                offenders.len(),
+                format_size(offenders.iter().map(|f| f.size).sum()),
+                age
+            );
+            result(format!("age <= {}", age), offenders.is_empty(), message, &offenders);
+        }
+        
+        if let Some(limit) = &rule.max_duplicate_waste {
+            let limit = parse_size(limit)?;
+            
+            let mut groups: HashMap<&str, Vec<&FileInfo>> = HashMap::new();
+            for file in &matched {
+                if let Some(hash) = &file.hash {
+                    groups.entry(hash.as_str()).or_default().push(file);
+                }
+            }
+            
+            // Every copy but the first by path counts as waste
+            let mut copies: Vec<&FileInfo> = Vec::new();
+            for group in groups.values_mut().filter(|group| group.len() > 1) {
+                group.sort_by(|a, b| a.path.cmp(&b.path));
+                copies.extend(&group[1..]);
+            }
+            copies.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+            
+            let wasted: u64 = copies.iter().map(|f| f.size).sum();
+            result(
+                format!("duplicate waste <= {}", format_size(limit)),
+                wasted <= limit,
+                format!("{} wasted by {} copies", format_size(wasted), copies.len()),
+                &copies,
+            );
+        }
+    }
+    
+    Ok(results)
+}
+
+// Display check results with the offending files of each failure
+fn display_check_results(results: &[CheckResult], dir: &Path) {
+    println!("\n{}", format!("Policy Check: {}", dir.display()).bold().underline());
+    
+    for result in results {
+        let status = if result.passed { "PASS".green().bold() } else { "FAIL".red().bold() };
+        println!("{}  {}: {} ({})", status, result.rule, result.limit, result.message);
+        
+        for file in result.files.iter().take(10) {
+            println!("        {:>12}  {}", format_size(file.size).yellow(), file.path.display());
+        }
+        if result.files.len() > 10 {
+            println!("        ... and {} more", result.files.len() - 10);
+        }
+    }
+    
+    let failed = results.iter().filter(|r| !r.passed).count();
+    if failed == 0 {
+        println!("\n{}", format!("All {} checks passed.", results.len()).bold().green());
+    } else {
+        println!("\n{}", format!("{} of {} checks failed.", failed, results.len()).bold().red());
+    }
+}
+
+// Organization methods offered by the interactive browser
+const UI_ORGANIZE_METHODS: &[&str] = &["type", "date", "exif-date", "doc-date", "ext", "music"];
+
+// Key reminder shown in the browser's status line
+const UI_HELP: &str = "↑↓ select  →/enter open  ← back  1-3 view  space mark  a all  d dupes  u unmark  t trash  m move  o organize  q quit";
+
+impl UiApp {
+    // Set up the browser over a finished scan
+    fn new(root: &Path, files: Vec<FileInfo>, hashed: bool) -> UiApp {
+        let mut app = UiApp {
+            root: root.to_path_buf(),
+            files: Vec::new(),
+            tree: DirectoryNode::default(),
+            duplicate_groups: Vec::new(),
+            hashed,
+            view: UiView::Tree,
+            directory: PathBuf::new(),
+            category: None,
+            group: None,
+            entries: Vec::new(),
+            table: ratatui::widgets::TableState::default(),
+            marked: HashSet::new(),
+            mode: UiMode::Browse,
+            message: None,
+        };
+        app.load(files);
+        app
+    }
+    
+    // Replace the scanned files, staying in the current location if it still exists
+    fn load(&mut self, files: Vec<FileInfo>) {
+        let index: HashMap<&Path, usize> = files.iter().enumerate().map(|(i, f)| (f.path.as_path(), i)).collect();
+        let mut groups: Vec<Vec<usize>> = find_duplicates(&files)
+            .values()
+            .map(|group| {
+                let mut indices: Vec<usize> = group.iter().map(|f| index[f.path.as_path()]).collect();
+                indices.sort_by(|&a, &b| files[a].path.cmp(&files[b].path));
+                indices
+            })
+            .collect();
+        
+        // Most wasted space first
+        let wasted = |group: &Vec<usize>| files[group[0]].size * (group.len() as u64 - 1);
+        groups.sort_by(|a, b| wasted(b).cmp(&wasted(a)).then_with(|| files[a[0]].path.cmp(&files[b[0]].path)));
+        
+        self.tree = build_directory_tree(&self.root, &files);
+        self.duplicate_groups = groups;
+        self.files = files;
+        self.marked.clear();
+        
+        if self.directory_node(&self.directory).is_none() {
+            self.directory = PathBuf::new();
+        }
+        self.group = self.group.filter(|&group| group < self.duplicate_groups.len());
+        self.refresh();
+    }
+    
+    // Node of a directory relative to the root
+    fn directory_node(&self, directory: &Path) -> Option<&DirectoryNode> {
+        directory
+            .components()
+            .try_fold(&self.tree, |node, component| node.children.get(component.as_os_str().to_string_lossy().as_ref()))
+    }
+    
+    // Row for a single scanned file
+    fn file_entry(&self, index: usize) -> UiEntry {
+        let file = &self.files[index];
+        let name = file.path.file_name().unwrap_or(file.path.as_os_str()).to_string_lossy();
+        
+        UiEntry {
+            name: if matches!(file.category, FileCategory::Project) { format!("{} [project]", name) } else { name.to_string() },
+            size: file.size,
+            files: 1,
+            kind: UiEntryKind::File(index),
+        }
+    }
+    
+    // Rebuild the rows for the current view and location, largest first
+    fn refresh(&mut self) {
+        let mut entries: Vec<UiEntry> = match self.view {
+            UiView::Tree => {
+                let mut entries: Vec<UiEntry> = self
+                    .directory_node(&self.directory)
+                    .map(|node| {
+                        node.children
+                            .iter()
+                            .map(|(name, child)| UiEntry {
+                                name: format!("{}/", name),
+                                size: child.size,
+                                files: child.files,
+                                kind: UiEntryKind::Directory(self.directory.join(name)),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                
+                let here = self.root.join(&self.directory);
+                entries.extend(
+                    (0..self.files.len())
+                        .filter(|&i| self.files[i].path.parent() == Some(here.as_path()))
+                        .map(|i| self.file_entry(i)),
+                );
+                entries
+            }
+            UiView::Categories => match &self.category {
+                Some(category) => (0..self.files.len())
+                    .filter(|&i| &category_label(&self.files[i], false) == category)
+                    .map(|i| self.file_entry(i))
+                    .collect(),
+                None => {
+                    let mut totals: HashMap<String, (u64, usize)> = HashMap::new();
+                    for file in &self.files {
+                        let total = totals.entry(category_label(file, false)).or_insert((0, 0));
+                        total.0 += file.size;
+                        total.1 += 1;
+                    }
+                    
+                    totals
+                        .into_iter()
+                        .map(|(category, (size, files))| UiEntry {
+                            name: category.clone(),
+                            size,
+                            files,
+                            kind: UiEntryKind::Category(category),
+                        })
+                        .collect()
+                }
+            },
+            UiView::Duplicates => match self.group {
+                Some(group) => self.duplicate_groups[group].iter().map(|&i| self.file_entry(i)).collect(),
+                None => self
+                    .duplicate_groups
+                    .iter()
+                    .enumerate()
+                    .map(|(index, group)| {
+                        let first = self.file_entry(group[0]);
+                        UiEntry {
+                            name: format!("{} ({} copies)", first.name, group.len()),
+                            size: first.size * (group.len() as u64 - 1),
+                            files: group.len(),
+                            kind: UiEntryKind::DuplicateGroup(index),
+                        }
+                    })
+                    .collect(),
+            },
+        };
+        
+        entries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        self.entries = entries;
+        
+        let selected = self.table.selected().unwrap_or(0).min(self.entries.len().saturating_sub(1));
+        self.table.select((!self.entries.is_empty()).then_some(selected));
+    }
+    
+    // Title of the list being shown
+    fn location(&self) -> String {
+        match (self.view, &self.category, self.group) {
+            (UiView::Tree, _, _) if self.directory.as_os_str().is_empty() => self.root.display().to_string(),
+            (UiView::Tree, _, _) => self.root.join(&self.directory).display().to_string(),
+            (UiView::Categories, Some(category), _) => format!("Categories / {}", category),
+            (UiView::Categories, None, _) => "Categories".to_string(),
+            (UiView::Duplicates, _, Some(group)) => format!("Duplicates / group {}", group + 1),
+            (UiView::Duplicates, _, None) => "Duplicates (size is wasted space)".to_string(),
+        }
+    }
+    
+    // The highlighted row, if any
+    fn selected_entry(&self) -> Option<&UiEntry> {
+        self.table.selected().and_then(|i| self.entries.get(i))
+    }
+    
+    // Move the highlight by `delta` rows, stopping at either end
+    fn move_selection(&mut self, delta: isize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let last = self.entries.len() as isize - 1;
+        self.table.select(Some(current.saturating_add(delta).clamp(0, last) as usize));
+    }
+    
+    // Descend into the highlighted directory, category or duplicate group
+    fn open(&mut self) {
+        let kind = match self.selected_entry() {
+            Some(entry) => entry.kind.clone(),
+            None => return,
+        };
+        
+        match kind {
+            UiEntryKind::Directory(path) => self.directory = path,
+            UiEntryKind::Category(category) => self.category = Some(category),
+            UiEntryKind::DuplicateGroup(group) => self.group = Some(group),
+            UiEntryKind::File(_) => return,
+        }
+        
+        self.table = ratatui::widgets::TableState::default().with_selected(Some(0));
+        self.refresh();
+    }
+    
+    // Go up one level, highlighting the entry we came from
+    fn back(&mut self) {
+        let previous = match self.view {
+            UiView::Tree if !self.directory.as_os_str().is_empty() => {
+                let previous = UiEntryKind::Directory(self.directory.clone());
+                self.directory.pop();
+                previous
+            }
+            UiView::Categories if self.category.is_some() => UiEntryKind::Category(self.category.take().unwrap()),
+            UiView::Duplicates if self.group.is_some() => UiEntryKind::DuplicateGroup(self.group.take().unwrap()),
+            _ => return,
+        };
+        
+        self.table = ratatui::widgets::TableState::default();
+        self.refresh();
+        let position = self.entries.iter().position(|entry| entry.kind == previous);
+        self.table.select(position.or((!self.entries.is_empty()).then_some(0)));
+    }
+    
+    // Switch to another view at its top level position
+    fn switch_view(&mut self, view: UiView) {
+        self.view = view;
+        self.table = ratatui::widgets::TableState::default().with_selected(Some(0));
+        self.refresh();
+    }
+    
+    // Indices of the scanned files a row stands for
+    fn entry_files(&self, entry: &UiEntry) -> Vec<usize> {
+        match &entry.kind {
+            UiEntryKind::File(index) => vec![*index],
+            UiEntryKind::Directory(path) => {
+                let directory = self.root.join(path);
+                (0..self.files.len()).filter(|&i| self.files[i].path.starts_with(&directory)).collect()
+            }
+            UiEntryKind::Category(category) => (0..self.files.len())
+                .filter(|&i| &category_label(&self.files[i], false) == category)
+                .collect(),
+            UiEntryKind::DuplicateGroup(group) => self.duplicate_groups[*group].clone(),
+        }
+    }
+    
+    // Mark column for a row: '*' for a marked file, '+' for a group holding marked files
+    fn entry_mark(&self, entry: &UiEntry) -> &'static str {
+        let marked = match &entry.kind {
+            UiEntryKind::File(index) => return if self.marked.contains(index) { "*" } else { " " },
+            UiEntryKind::Directory(path) => {
+                let directory = self.root.join(path);
+                self.marked.iter().any(|&i| self.files[i].path.starts_with(&directory))
+            }
+            UiEntryKind::Category(category) => self.marked.iter().any(|&i| &category_label(&self.files[i], false) == category),
+            UiEntryKind::DuplicateGroup(group) => self.duplicate_groups[*group].iter().any(|i| self.marked.contains(i)),
+        };
+        
+        if marked { "+" } else { " " }
+    }
+    
+    // Mark the files under the highlighted row, or unmark them if all are marked
+    fn toggle_mark(&mut self) {
+        let files = match self.selected_entry() {
+            Some(entry) => self.entry_files(entry),
+            None => return,
+        };
+        
+        if files.iter().all(|i| self.marked.contains(i)) {
+            for i in &files {
+                self.marked.remove(i);
+            }
+        } else {
+            self.marked.extend(files);
+        }
+        self.move_selection(1);
+    }
+    
+    // Mark every file in the current list
+    fn mark_all(&mut self) {
+        let files: Vec<usize> = self.entries.iter().flat_map(|entry| self.entry_files(entry)).collect();
+        self.marked.extend(files);
+    }
+    
+    // Mark every copy but the first of the open duplicate group, or of all groups
+    fn mark_duplicates(&mut self) {
+        let groups = match self.group {
+            Some(group) => vec![group],
+            None => (0..self.duplicate_groups.len()).collect(),
+        };
+        
+        for group in groups {
+            self.marked.extend(self.duplicate_groups[group].iter().skip(1).copied());
+        }
+    }
+    
+    // The marked files in scan order
+    fn marked_files(&self) -> Vec<&FileInfo> {
+        let mut indices: Vec<usize> = self.marked.iter().copied().collect();
+        indices.sort_unstable();
+        indices.into_iter().map(|i| &self.files[i]).collect()
+    }
+    
+    // Count and total size of the marked files, for the status line
+    fn marked_summary(&self) -> String {
+        let size: u64 = self.marked.iter().map(|&i| self.files[i].size).sum();
+        format!("{} marked files ({})", self.marked.len(), format_size(size))
+    }
+    
+    // Marked files that are safe to act on, and the rest with the reason they were skipped.
+    // Every file must be unchanged since the scan, and a marked duplicate must still match
+    // an unmarked copy byte for byte, so acting on it never loses the last copy.
+    fn verified_marked_files(&self) -> (Vec<FileInfo>, Vec<(PathBuf, String)>) {
+        let marked = self.marked_files();
+        let marked_paths: HashSet<&Path> = marked.iter().map(|f| f.path.as_path()).collect();
+        
+        let mut groups: DuplicateGroups = HashMap::new();
+        for (hash, group) in find_duplicates(&self.files) {
+            let (picked, kept): (Vec<&FileInfo>, Vec<&FileInfo>) =
+                group.into_iter().partition(|f| marked_paths.contains(f.path.as_path()));
+            if let (Some(&keeper), false) = (kept.first(), picked.is_empty()) {
+                groups.insert(hash, std::iter::once(keeper).chain(picked).collect());
+            }
+        }
+        let grouped: HashSet<&Path> = groups.values().flat_map(|g| g.iter().skip(1)).map(|f| f.path.as_path()).collect();
+        
+        let (confirmed, failed) = verify_duplicates(groups);
+        let confirmed: HashSet<&Path> = confirmed.values().flat_map(|g| g.iter().skip(1)).map(|f| f.path.as_path()).collect();
+        
+        let mut skipped: Vec<(PathBuf, String)> = failed
+            .into_iter()
+            .filter(|(file, _)| marked_paths.contains(file.path.as_path()))
+            .map(|(file, reason)| (file.path.clone(), reason))
+            .collect();
+        let mut ready = Vec::new();
+        
+        for file in marked {
+            let path = file.path.as_path();
+            if grouped.contains(path) {
+                if confirmed.contains(path) {
+                    ready.push(file.clone());
+                }
+            } else if unchanged_since_scan(file) {
+                ready.push(file.clone());
+            } else {
+                skipped.push((file.path.clone(), "changed since scan".to_string()));
+            }
+        }
+        
+        skipped.sort();
+        (ready, skipped)
+    }
+    
+    // Question asked before running an action
+    fn confirm_text(&self, action: &UiAction) -> String {
+        match action {
+            UiAction::Trash => format!("Move {} to the trash? [y/N]", self.marked_summary()),
+            UiAction::Move(target) => format!("Move {} to {}? [y/N]", self.marked_summary(), target.display()),
+            UiAction::Organize(target, method) => {
+                format!("Organize {} into {} by {}? [y/N]", self.marked_summary(), target.display(), method)
+            }
+        }
+    }
+    
+    // Handle a key press in any mode
+    fn handle_key(&mut self, key: ratatui::crossterm::event::KeyEvent) -> UiStep {
+        use ratatui::crossterm::event::{KeyCode, KeyModifiers};
+        
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return UiStep::Quit;
+        }
+        
+        match std::mem::replace(&mut self.mode, UiMode::Browse) {
+            UiMode::Browse => self.handle_browse_key(key.code),
+            UiMode::Prompt(prompt, mut input) => {
+                match key.code {
+                    KeyCode::Esc => self.message = Some("Cancelled".to_string()),
+                    KeyCode::Enter => self.submit_prompt(prompt, input.trim()),
+                    KeyCode::Backspace => {
+                        input.pop();
+                        self.mode = UiMode::Prompt(prompt, input);
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                        self.mode = UiMode::Prompt(prompt, input);
+                    }
+                    _ => self.mode = UiMode::Prompt(prompt, input),
+                }
+                UiStep::Continue
+            }
+            UiMode::Confirm(action) => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => UiStep::Execute(action),
+                _ => {
+                    self.message = Some("Cancelled".to_string());
+                    UiStep::Continue
+                }
+            },
+        }
+    }
+    
+    // Handle a key press while browsing
+    fn handle_browse_key(&mut self, code: ratatui::crossterm::event::KeyCode) -> UiStep {
+        use ratatui::crossterm::event::KeyCode;
+        
+        self.message = None;
+        match code {
+            KeyCode::Char('q') => return UiStep::Quit,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-20),
+            KeyCode::PageDown => self.move_selection(20),
+            KeyCode::Home => self.move_selection(isize::MIN),
+            KeyCode::End => self.move_selection(isize::MAX),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open(),
+            KeyCode::Left | KeyCode::Backspace | KeyCode::Esc | KeyCode::Char('h') => self.back(),
+            KeyCode::Char('1') => self.switch_view(UiView::Tree),
+            KeyCode::Char('2') => self.switch_view(UiView::Categories),
+            KeyCode::Char('3') => self.switch_view(UiView::Duplicates),
+            KeyCode::Tab => self.switch_view(match self.view {
+                UiView::Tree => UiView::Categories,
+                UiView::Categories => UiView::Duplicates,
+                UiView::Duplicates => UiView::Tree,
+            }),
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('a') => self.mark_all(),
+            KeyCode::Char('d') => self.mark_duplicates(),
+            KeyCode::Char('u') => self.marked.clear(),
+            KeyCode::Char('t') | KeyCode::Char('m') | KeyCode::Char('o') if self.marked.is_empty() => {
+                self.message = Some("Nothing is marked (space marks the highlighted entry)".to_string());
+            }
+            KeyCode::Char('t') => self.mode = UiMode::Confirm(UiAction::Trash),
+            KeyCode::Char('m') => self.mode = UiMode::Prompt(UiPrompt::MoveTarget, String::new()),
+            KeyCode::Char('o') => {
+                self.mode = UiMode::Prompt(UiPrompt::OrganizeTarget, self.root.display().to_string());
+            }
+            _ => {}
+        }
+        
+        UiStep::Continue
+    }
     
-    // Add to front
-    config.recent_directories.insert(0, dir_path);
+    // Move on from a prompt once its text is entered
+    fn submit_prompt(&mut self, prompt: UiPrompt, input: &str) {
+        if input.is_empty() {
+            self.message = Some("Cancelled".to_string());
+            return;
+        }
+        
+        match prompt {
+            UiPrompt::MoveTarget => {
+                self.mode = UiMode::Confirm(UiAction::Move(PathBuf::from(expand_home(input))));
+            }
+            UiPrompt::OrganizeTarget => {
+                self.mode = UiMode::Prompt(UiPrompt::OrganizeMethod(PathBuf::from(expand_home(input))), "type".to_string());
+            }
+            UiPrompt::OrganizeMethod(target) if UI_ORGANIZE_METHODS.contains(&input) => {
+                self.mode = UiMode::Confirm(UiAction::Organize(target, input.to_string()));
+            }
+            UiPrompt::OrganizeMethod(_) => {
+                self.message = Some(format!("Unknown method '{}' (use {})", input, UI_ORGANIZE_METHODS.join(", ")));
+            }
+        }
+    }
+}
+
+// Draw the interactive browser
+fn draw_ui(frame: &mut ratatui::Frame, app: &mut UiApp) {
+    use ratatui::layout::{Alignment, Constraint, Layout};
+    use ratatui::style::{Modifier, Style, Stylize};
+    use ratatui::text::{Line, Span};
+    use ratatui::widgets::{Block, Cell, Paragraph, Row, Table};
     
-    // Keep only the 10 most recent
-    if config.recent_directories.len() > 10 {
-        config.recent_directories.truncate(10);
+    let [header_area, list_area, status_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    
+    // Header: root, totals and the view tabs
+    let total_size: u64 = app.files.iter().map(|f| f.size).sum();
+    let mut header = vec![
+        Span::from(" tidyfs ").bold().reversed(),
+        Span::from(format!(" {}  {} in {} files   ", app.root.display(), format_size(total_size), app.files.len())),
+    ];
+    for (key, label, view) in [("1", "Directories", UiView::Tree), ("2", "Categories", UiView::Categories), ("3", "Duplicates", UiView::Duplicates)] {
+        let tab = Span::from(format!(" {} {} ", key, label));
+        header.push(if app.view == view { tab.bold().reversed() } else { tab });
     }
+    frame.render_widget(Line::from(header), header_area);
     
-    save_config(config)
+    let block = Block::bordered().title(format!(" {} ", app.location()));
+    if app.entries.is_empty() {
+        let text = match app.view {
+            UiView::Duplicates if !app.hashed => "Duplicate detection needs hashes: start with tidyfs ui -d",
+            UiView::Duplicates => "No duplicate files found.",
+            _ => "Nothing here.",
+        };
+        frame.render_widget(Paragraph::new(text).block(block), list_area);
+    } else {
+        let list_total: u64 = app.entries.iter().map(|e| e.size).sum();
+        let rows: Vec<Row> = app
+            .entries
+            .iter()
+            .map(|entry| {
+                let share = if list_total > 0 { entry.size as f64 / list_total as f64 } else { 0.0 };
+                let bar = "#".repeat((share * 10.0).round() as usize);
+                let name = match entry.kind {
+                    UiEntryKind::File(_) => Span::from(entry.name.clone()),
+                    _ => Span::from(entry.name.clone()).bold(),
+                };
+                
+                Row::new(vec![
+                    Cell::from(app.entry_mark(entry)),
+                    Cell::from(Line::from(format_size(entry.size)).alignment(Alignment::Right)),
+                    Cell::from(format!("[{:<10}]", bar)),
+                    Cell::from(Line::from(format!("{:.1}%", share * 100.0)).alignment(Alignment::Right)),
+                    Cell::from(Line::from(entry.files.to_string()).alignment(Alignment::Right)),
+                    Cell::from(name),
+                ])
+            })
+            .collect();
+        
+        let widths = [
+            Constraint::Length(1),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(vec!["", "Size", "", "%", "Files", "Name"]).bold())
+            .block(block)
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, list_area, &mut app.table);
+    }
+    
+    // Status line: prompt, confirmation, last message or key help
+    let status = match &app.mode {
+        UiMode::Prompt(prompt, input) => {
+            let label = match prompt {
+                UiPrompt::MoveTarget => "Move marked files to",
+                UiPrompt::OrganizeTarget => "Organize marked files into",
+                UiPrompt::OrganizeMethod(_) => "Organize by (type, date, exif-date, doc-date, ext, music)",
+            };
+            Line::from(format!("{}: {}_", label, input)).bold()
+        }
+        UiMode::Confirm(action) => Line::from(app.confirm_text(action)).bold().yellow(),
+        UiMode::Browse => {
+            let mut spans = vec![Span::from(app.message.clone().unwrap_or_else(|| UI_HELP.to_string()))];
+            if !app.marked.is_empty() {
+                spans.push(Span::from(format!("  [{}]", app.marked_summary())).bold());
+            }
+            Line::from(spans)
+        }
+    };
+    frame.render_widget(status, status_area);
 }
 
-// Display storage usage report
-fn display_storage_report(files: &[FileInfo]) {
-    let total_size: u64 = files.iter().map(|f| f.size).sum();
-    let file_count = files.len();
+// Browse scan results in a full-screen terminal UI
+fn run_ui(root: &Path, config: &TidyConfig, options: &ScanOptions) -> Result<(), Box<dyn Error>> {
+    let files = scan_directory(root, config, options)?;
+    let mut app = UiApp::new(root, files, options.calculate_hashes);
     
-    // Group by category
-    let mut category_sizes: HashMap<String, u64> = HashMap::new();
-    let mut category_counts: HashMap<String, usize> = HashMap::new();
+    let mut terminal = ratatui::try_init()?;
+    let result = ui_event_loop(&mut terminal, &mut app, config, options);
+    ratatui::restore();
     
-    for file in files {
-        let category = match &file.category {
-            FileCategory::Document => "Documents".to_string(),
-            FileCategory::Image => "Images".to_string(),
-            FileCategory::Video => "Videos".to_string(),
-            FileCategory::Audio => "Audio".to_string(),
-            FileCategory::Archive => "Archives".to_string(),
-            FileCategory::Code => "Code".to_string(),
-            FileCategory::Executable => "Executables".to_string(),
-            FileCategory::Other(ext) => {
-                if ext == "unknown" {
-                    "Unknown".to_string()
-                } else {
-                    format!("Other (.{})", ext)
-                }
+    result
+}
+
+// Draw and handle keys until the user quits
+fn ui_event_loop(
+    terminal: &mut ratatui::DefaultTerminal,
+    app: &mut UiApp,
+    config: &TidyConfig,
+    options: &ScanOptions,
+) -> Result<(), Box<dyn Error>> {
+    use ratatui::crossterm::event::{self, Event, KeyEventKind};
+    
+    loop {
+        terminal.draw(|frame| draw_ui(frame, app))?;
+        
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            
+            match app.handle_key(key) {
+                UiStep::Continue => {}
+                UiStep::Quit => return Ok(()),
+                UiStep::Execute(action) => run_ui_action(terminal, app, action, config, options)?,
             }
+        }
+    }
+}
+
+// Carry out a confirmed action on the marked files
+fn run_ui_action(
+    terminal: &mut ratatui::DefaultTerminal,
+    app: &mut UiApp,
+    action: UiAction,
+    config: &TidyConfig,
+    options: &ScanOptions,
+) -> Result<(), Box<dyn Error>> {
+    let (marked, skipped) = app.verified_marked_files();
+    let summary = format!("{} files ({})", marked.len(), format_size(marked.iter().map(|f| f.size).sum()));
+    
+    if action == UiAction::Trash && skipped.is_empty() {
+        let message = match trash::delete_all(marked.iter().map(|f| &f.path)) {
+            Ok(()) => format!("Moved {} to the trash", summary),
+            Err(e) => format!("Could not move files to the trash: {}", e),
         };
         
-        *category_sizes.entry(category.clone()).or_insert(0) += file.size;
-        *category_counts.entry(category).or_insert(0) += 1;
+        // Trashing only removes files, so there is nothing to rescan
+        let files = std::mem::take(&mut app.files).into_iter().filter(|f| f.path.exists()).collect();
+        app.load(files);
+        app.message = Some(message);
+        return Ok(());
     }
     
-    // Sort categories by size descending
-    let mut categories: Vec<(String, u64, usize)> = category_sizes
-        .iter()
-        .map(|(k, v)| (k.clone(), *v, *category_counts.get(k).unwrap_or(&0)))
-        .collect();
+    // Organize prints its own progress, and skipped files need more room than the status line,
+    // so hand the terminal back while it runs
+    ratatui::restore();
+    if !skipped.is_empty() {
+        println!(
+            "{} ({} marked files changed since the scan or failed verification)",
+            "Skipped".bold().yellow(),
+            skipped.len()
+        );
+        for (path, reason) in &skipped {
+            println!("  {} ({})", path.display(), reason.red());
+        }
+        println!();
+    }
     
-    categories.sort_by(|a, b| b.1.cmp(&a.1));
+    let outcome = match &action {
+        UiAction::Trash => trash::delete_all(marked.iter().map(|f| &f.path))
+            .map(|()| println!("Moved {} to the trash", summary))
+            .map_err(|e| e.into()),
+        UiAction::Move(target) => organize_files(&marked, target, "flat", None, false, false, config),
+        UiAction::Organize(target, method) => organize_marked(&marked, target, method, config),
+    };
+    if let Err(e) = &outcome {
+        println!("{} {}", "Error:".red().bold(), e);
+    }
     
-    println!("\n{}", "Storage Usage Report".bold().underline());
-    println!(
-        "Total: {} files, {}",
-        file_count,
-        format_size(total_size).bold()
-    );
+    // Files may have moved anywhere below the root, so scan again
+    let rescan = scan_directory(&app.root, config, options);
     
-    println!("\n{:<20} {:<15} {:<10} {:<10}", 
-             "Category".bold(), 
-             "Size".bold(), 
-             "Files".bold(), 
-             "% of Total".bold());
+    println!("\nPress Enter to return to the browser");
+    std::io::stdin().read_line(&mut String::new())?;
     
-    println!("{}", "-".repeat(55));
+    ratatui::crossterm::terminal::enable_raw_mode()?;
+    ratatui::crossterm::execute!(std::io::stdout(), ratatui::crossterm::terminal::EnterAlternateScreen)?;
+    terminal.clear()?;
     
-    for (category, size, count) in categories {
-        let percentage = (size as f64 / total_size as f64) * 100.0;
-        
-        println!(
-            "{:<20} {:<15} {:<10} {:.1}%",
-            category,
-            format_size(size),
-            count,
-            percentage
-        );
+    match rescan {
+        Ok(files) => app.load(files),
+        Err(e) => app.message = Some(format!("Rescan failed: {}", e)),
     }
+    if let Err(e) = outcome {
+        app.message = Some(format!("Failed: {}", e));
+    } else if app.message.is_none() {
+        let skipped = if skipped.is_empty() { String::new() } else { format!(", skipped {}", skipped.len()) };
+        app.message = Some(format!("Done with {}{}", summary, skipped));
+    }
+    
+    Ok(())
+}
+
+// Organize hand-picked files like `organize --by METHOD`, reading metadata where the method needs it
+fn organize_marked(files: &[FileInfo], target: &Path, method: &str, config: &TidyConfig) -> Result<(), Box<dyn Error>> {
+    let template = (method == "music").then_some(MUSIC_TEMPLATE);
+    let options = ScanOptions {
+        read_metadata: organize_metadata(method, template.is_some()),
+        ..ScanOptions::default()
+    };
+    let needs_metadata = options.read_metadata != MetadataKinds::default();
     
-    // Find largest files
-    let mut largest_files = files.to_vec();
-    largest_files.sort_by(|a, b| b.size.cmp(&a.size));
-    largest_files.truncate(5);
+    let mut files: Vec<FileInfo> = files
+        .iter()
+        .map(|file| {
+            if needs_metadata && !matches!(file.category, FileCategory::Project) {
+                get_file_info(&file.path, config, &options).unwrap_or_else(|_| file.clone())
+            } else {
+                file.clone()
+            }
+        })
+        .collect();
     
-    println!("\n{}", "Largest Files:".bold().underline());
-    for file in largest_files {
-        println!(
-            "{} ({})",
-            file.path.display().to_string().cyan(),
-            format_size(file.size).yellow()
-        );
+    // A music library only takes audio files
+    if method == "music" {
+        files.retain(|file| matches!(file.category, FileCategory::Audio));
     }
+    
+    organize_files(&files, target, method, template, false, false, config)
 }
 
 // Main function with CLI handling
@@ -509,59 +5873,384 @@ fn main() -> Result<(), Box<dyn Error>> {
             SubCommand::with_name("scan")
                 .about("Scan directory and show statistics")
                 .arg(
-                    Arg::with_name("dir")
-                        .help("Directory to scan")
-                        .default_value(".")
+                    Arg::with_name("dir")
+                        .help("Directory to scan")
+                        .default_value(".")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("recursive")
+                        .short("r")
+                        .long("recursive")
+                        .help("Scan subdirectories recursively"),
+                )
+                .arg(
+                    Arg::with_name("duplicates")
+                        .short("d")
+                        .long("duplicates")
+                        .help("Find duplicate files"),
+                )
+                .arg(
+                    Arg::with_name("paranoid")
+                        .long("paranoid")
+                        .requires("duplicates")
+                        .help("Byte-compare duplicates against the group keeper to confirm them"),
+                )
+                .arg(
+                    Arg::with_name("report")
+                        .long("report")
+                        .requires("duplicates")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Write all duplicate groups to FILE (.json, .csv or .sh)"),
+                )
+                .arg(
+                    Arg::with_name("no-projects")
+                        .long("no-projects")
+                        .help("Scan inside project directories instead of treating them as single items"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .default_value("text")
+                        .help("Output format (text, json, ndjson, csv); structured formats write plain records to stdout"),
+                )
+                .arg(
+                    Arg::with_name("tree")
+                        .long("tree")
+                        .help("Show a directory tree sorted by size"),
+                )
+                .arg(
+                    Arg::with_name("depth")
+                        .long("depth")
+                        .takes_value(true)
+                        .default_value("3")
+                        .help("Number of directory levels shown by --tree"),
+                )
+                .arg(
+                    Arg::with_name("min-percent")
+                        .long("min-percent")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Fold --tree entries smaller than this percentage of the total"),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Number of entries in the largest files, largest directories and stale lists (default 5, 10 for stale files)"),
+                )
+                .arg(
+                    Arg::with_name("top-per-category")
+                        .long("top-per-category")
+                        .help("Also list the largest files of each category"),
+                )
+                .arg(
+                    Arg::with_name("ages")
+                        .long("ages")
+                        .help("Show file ages overall and per category, and the largest stale files"),
+                )
+                .arg(
+                    Arg::with_name("stale-years")
+                        .long("stale-years")
+                        .takes_value(true)
+                        .default_value("2")
+                        .help("With --ages, list the largest files untouched for more than this many years"),
+                )
+                .arg(
+                    Arg::with_name("html")
+                        .long("html")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Write a self-contained HTML report with charts to FILE"),
+                )
+                .arg(
+                    Arg::with_name("prometheus")
+                        .long("prometheus")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Write Prometheus gauges for node_exporter's textfile collector to FILE"),
+                )
+                .arg(
+                    Arg::with_name("save-snapshot")
+                        .long("save-snapshot")
+                        .help("Save the scan (with hashes) as a snapshot for later comparison"),
+                )
+                .arg(
+                    Arg::with_name("metadata")
+                        .long("metadata")
+                        .help("Read embedded metadata such as EXIF camera and date"),
+                )
+                .arg(
+                    Arg::with_name("min-duration")
+                        .long("min-duration")
+                        .takes_value(true)
+                        .help("Only include videos at least this long (e.g. 90, 5m, 1.5h)"),
+                )
+                .arg(
+                    Arg::with_name("by-subcategory")
+                        .long("by-subcategory")
+                        .help("Break the report down by subcategory (e.g. Documents/Spreadsheets)"),
+                )
+                .arg(
+                    Arg::with_name("mismatched")
+                        .long("mismatched")
+                        .help("List files whose content doesn't match their extension"),
+                )
+                .arg(
+                    Arg::with_name("similar-images")
+                        .long("similar-images")
+                        .help("Find visually similar images (resized or re-encoded copies)"),
+                )
+                .arg(
+                    Arg::with_name("threshold")
+                        .long("threshold")
+                        .help("Maximum perceptual hash distance for similar images (0-64)")
+                        .takes_value(true)
+                        .default_value("10"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("organize")
+                .about("Organize files into folders")
+                .arg(
+                    Arg::with_name("dir")
+                        .help("Directory to organize")
+                        .default_value(".")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .help("Target directory for organized files")
+                        .short("t")
+                        .long("target")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("by")
+                        .help("Organization method (type, date, exif-date, doc-date, ext, music, flat)")
+                        .short("b")
+                        .long("by")
+                        .takes_value(true)
+                        .default_value("type"),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("Show what would be done without making changes")
+                        .short("n")
+                        .long("dry-run"),
+                )
+                .arg(
+                    Arg::with_name("recursive")
+                        .short("r")
+                        .long("recursive")
+                        .help("Process subdirectories recursively"),
+                )
+                .arg(
+                    Arg::with_name("no-projects")
+                        .long("no-projects")
+                        .help("Organize files inside project directories instead of moving projects whole"),
+                )
+                .arg(
+                    Arg::with_name("nested")
+                        .long("nested")
+                        .help("With --by type, sort into subcategory folders (e.g. Documents/Spreadsheets)"),
+                )
+                .arg(
+                    Arg::with_name("template")
+                        .long("template")
+                        .takes_value(true)
+                        .help("Destination path template, e.g. \"{artist}/{album}/{track:02} - {title}.{ext}\""),
+                )
+                .arg(
+                    Arg::with_name("min-duration")
+                        .long("min-duration")
+                        .takes_value(true)
+                        .help("Only organize videos at least this long (e.g. 90, 5m, 1.5h)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fix-extensions")
+                .about("Rename files so their extension matches their content")
+                .arg(
+                    Arg::with_name("dir")
+                        .help("Directory to fix")
+                        .default_value(".")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("Show what would be renamed without making changes")
+                        .short("n")
+                        .long("dry-run"),
+                )
+                .arg(
+                    Arg::with_name("recursive")
+                        .short("r")
+                        .long("recursive")
+                        .help("Process subdirectories recursively"),
+                )
+                .arg(
+                    Arg::with_name("undo")
+                        .long("undo")
+                        .help("Undo the renames recorded in a journal file")
+                        .takes_value(true)
+                        .value_name("JOURNAL"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("explain-category")
+                .about("Show which category definition matches a file")
+                .arg(
+                    Arg::with_name("file")
+                        .help("File to explain")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compare two directory trees by content")
+                .arg(
+                    Arg::with_name("a")
+                        .help("Source directory (A)")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("b")
+                        .help("Directory to compare against (B)")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Output the comparison as JSON"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ui")
+                .about("Browse scan results in a full-screen terminal UI")
+                .arg(
+                    Arg::with_name("dir")
+                        .help("Directory to browse")
+                        .default_value(".")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("duplicates")
+                        .short("d")
+                        .long("duplicates")
+                        .help("Hash files so the duplicates view can be used"),
+                )
+                .arg(
+                    Arg::with_name("no-projects")
+                        .long("no-projects")
+                        .help("Look inside project directories instead of treating each as one item"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compare two saved scan snapshots")
+                .arg(
+                    Arg::with_name("a")
+                        .help("Older snapshot (path or file name in the snapshot directory)")
+                        .required_unless("list")
                         .index(1),
                 )
                 .arg(
-                    Arg::with_name("recursive")
-                        .short("r")
-                        .long("recursive")
-                        .help("Scan subdirectories recursively"),
+                    Arg::with_name("b")
+                        .help("Newer snapshot (path or file name in the snapshot directory)")
+                        .required_unless("list")
+                        .index(2),
                 )
                 .arg(
-                    Arg::with_name("duplicates")
-                        .short("d")
-                        .long("duplicates")
-                        .help("Find duplicate files"),
+                    Arg::with_name("list")
+                        .long("list")
+                        .help("List saved snapshots"),
+                )
+                .arg(
+                    Arg::with_name("depth")
+                        .long("depth")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("1")
+                        .help("Directory levels used for the per-directory size change"),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("10")
+                        .help("Number of entries shown per list"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Output the comparison as JSON"),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("organize")
-                .about("Organize files into folders")
+            SubCommand::with_name("check")
+                .about("Scan a directory and fail when it breaks size, age or duplicate limits")
                 .arg(
                     Arg::with_name("dir")
-                        .help("Directory to organize")
+                        .help("Directory to check")
                         .default_value(".")
                         .index(1),
                 )
                 .arg(
-                    Arg::with_name("target")
-                        .help("Target directory for organized files")
-                        .short("t")
-                        .long("target")
-                        .takes_value(true),
+                    Arg::with_name("rules")
+                        .long("rules")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Read rules from a JSON file"),
                 )
                 .arg(
-                    Arg::with_name("by")
-                        .help("Organization method (type, date, ext)")
-                        .short("b")
-                        .long("by")
+                    Arg::with_name("max-size")
+                        .long("max-size")
                         .takes_value(true)
-                        .default_value("type"),
+                        .value_name("[CATEGORY=]SIZE")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Limit the total size of a category, or of everything (repeatable)"),
                 )
                 .arg(
-                    Arg::with_name("dry-run")
-                        .help("Show what would be done without making changes")
-                        .short("n")
-                        .long("dry-run"),
+                    Arg::with_name("max-files")
+                        .long("max-files")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Limit the number of files"),
                 )
                 .arg(
-                    Arg::with_name("recursive")
-                        .short("r")
-                        .long("recursive")
-                        .help("Process subdirectories recursively"),
+                    Arg::with_name("max-file-size")
+                        .long("max-file-size")
+                        .takes_value(true)
+                        .value_name("SIZE")
+                        .help("Fail on any single file larger than SIZE"),
+                )
+                .arg(
+                    Arg::with_name("max-age")
+                        .long("max-age")
+                        .takes_value(true)
+                        .value_name("AGE")
+                        .help("Fail on files not modified within AGE (e.g. 30d, 6mo, 2y)"),
+                )
+                .arg(
+                    Arg::with_name("max-duplicate-waste")
+                        .long("max-duplicate-waste")
+                        .takes_value(true)
+                        .value_name("SIZE")
+                        .help("Limit the space taken by duplicate copies (hashes every file)"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Output the results as JSON"),
                 ),
         )
         .subcommand(
@@ -590,11 +6279,52 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .help("Add custom category (format: 'category:ext1,ext2')")
                         .takes_value(true),
                 )
+                .arg(
+                    Arg::with_name("priority")
+                        .long("priority")
+                        .requires("add-category")
+                        .help("Priority of the added category; higher wins, built-ins are 0")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .requires("add-category")
+                        .help("Limit the added category to paths matching this glob (repeatable)")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("remove-category")
+                        .long("remove-category")
+                        .help("Remove a custom category by name")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("add-project-rule")
+                        .long("add-project-rule")
+                        .help("Move projects with a marker to a destination (format: 'marker:destination')")
+                        .takes_value(true),
+                )
                 .arg(
                     Arg::with_name("set-default-org")
                         .long("set-default-org")
                         .help("Set default organization method (type, date, ext)")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("set-sniff-mode")
+                        .long("set-sniff-mode")
+                        .help("Set content sniffing mode (off, fallback, primary)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("set-mmap-threshold")
+                        .long("set-mmap-threshold")
+                        .help("Hash files of at least this many MB with mmap and multiple threads")
+                        .takes_value(true),
                 ),
         )
         .get_matches();
@@ -609,23 +6339,175 @@ fn main() -> Result<(), Box<dyn Error>> {
             update_recent_directories(&mut config, dir)?;
               let recursive = scan_matches.is_present("recursive");
             let should_find_duplicates = scan_matches.is_present("duplicates");
+            let paranoid = scan_matches.is_present("paranoid");
+            let should_find_similar = scan_matches.is_present("similar-images");
+            let threshold: u32 = scan_matches
+                .value_of("threshold")
+                .unwrap()
+                .parse()
+                .ok()
+                .filter(|threshold| *threshold <= 64)
+                .ok_or("Threshold must be a number between 0 and 64")?;
+            let min_duration = scan_matches.value_of("min-duration").map(parse_duration).transpose()?;
+            let depth: usize = scan_matches
+                .value_of("depth")
+                .unwrap()
+                .parse()
+                .map_err(|_| "Depth must be a whole number")?;
+            let min_percent: f64 = scan_matches
+                .value_of("min-percent")
+                .unwrap()
+                .parse()
+                .map_err(|_| "Minimum percentage must be a number")?;
+            let stale_years: u64 = scan_matches
+                .value_of("stale-years")
+                .unwrap()
+                .parse()
+                .map_err(|_| "Stale years must be a whole number")?;
+            let top: Option<usize> = scan_matches
+                .value_of("top")
+                .map(|top| top.parse())
+                .transpose()
+                .map_err(|_| "Top must be a whole number")?;
+            let format = scan_matches.value_of("format").unwrap();
+            let structured = format != "text";
             
-            println!(
-                "{}",
-                format!("Scanning directory: {}", dir.display()).bold().green()
-            );
+            if !matches!(format, "text" | "json" | "ndjson" | "csv") {
+                return Err(format!("Unknown output format '{}' (use text, json, ndjson or csv)", format).into());
+            }
+            if structured && (scan_matches.is_present("mismatched") || should_find_similar) {
+                return Err("--mismatched and --similar-images are only available with --format text".into());
+            }
+            // Catch a bad report name before spending time on the scan
+            if let Some(report_path) = scan_matches.value_of("report") {
+                duplicate_report_format(Path::new(report_path))?;
+            }
+            
+            if structured {
+                // Keep stdout free of escape codes for other tools
+                colored::control::set_override(false);
+            } else {
+                println!(
+                    "{}",
+                    format!("Scanning directory: {}", dir.display()).bold().green()
+                );
+            }
+            
+            let options = ScanOptions {
+                recursive,
+                // Snapshots keep hashes so moved files can be recognized later
+                calculate_hashes: should_find_duplicates || scan_matches.is_present("save-snapshot"),
+                calculate_perceptual_hashes: should_find_similar,
+                detect_projects: !scan_matches.is_present("no-projects"),
+                read_metadata: if scan_matches.is_present("metadata") {
+                    MetadataKinds::ALL
+                } else {
+                    MetadataKinds { video: min_duration.is_some(), ..MetadataKinds::default() }
+                },
+                quiet: structured,
+            };
+            let scan_start = Instant::now();
+            let (mut files, scan_errors) = scan_directory_with_errors(dir, &config, &options)?;
+            
+            if let Some(min_duration) = min_duration {
+                files.retain(|file| meets_min_duration(file, min_duration));
+            }
+            
+            let mut skipped = Vec::new();
+            let duplicates = if should_find_duplicates {
+                let duplicates = find_duplicates(&files);
+                
+                if paranoid {
+                    let (confirmed, failed) = verify_duplicates(duplicates);
+                    skipped = failed;
+                    confirmed
+                } else {
+                    duplicates
+                }
+            } else {
+                HashMap::new()
+            };
+            
+            let defaults = ReportOptions::default();
+            let report_options = ReportOptions {
+                by_subcategory: scan_matches.is_present("by-subcategory"),
+                stale_years,
+                top: top.unwrap_or(defaults.top),
+                stale_top: top.unwrap_or(defaults.stale_top),
+            };
+            let mut report = build_storage_report(dir, &files, &report_options, should_find_duplicates.then_some(&duplicates));
+            report.skipped_duplicates = skipped
+                .iter()
+                .map(|(file, reason)| SkippedDuplicate {
+                    path: file.path.clone(),
+                    reason: reason.clone(),
+                })
+                .collect();
+            report.skipped_duplicates.sort_by(|a, b| a.path.cmp(&b.path));
+            
+            if let Some(html_path) = scan_matches.value_of("html") {
+                write_html_report(Path::new(html_path), dir, &files, &report)?;
+                if !structured {
+                    println!("HTML report written to {}", html_path.cyan());
+                }
+            }
             
-            let files = scan_directory(dir, &config, should_find_duplicates, recursive)?;
+            if let Some(prometheus_path) = scan_matches.value_of("prometheus") {
+                let stats = ScanRunStats {
+                    duration_secs: scan_start.elapsed().as_secs_f64(),
+                    errors: scan_errors,
+                    duplicates_checked: should_find_duplicates,
+                };
+                write_prometheus_metrics(Path::new(prometheus_path), dir, &files, &report, &stats)?;
+                if !structured {
+                    println!("Prometheus metrics written to {}", prometheus_path.cyan());
+                }
+            }
+            
+            if scan_matches.is_present("save-snapshot") {
+                let snapshot_path = save_snapshot(&snapshot_from_files(dir, &files))?;
+                if !structured {
+                    println!("Snapshot saved to {}", snapshot_path.display().to_string().cyan());
+                }
+            }
+            
+            if structured {
+                write_scan_output(format, dir, &files, &report)?;
+                
+                if let Some(report_path) = scan_matches.value_of("report") {
+                    write_duplicate_report(&duplicates, Path::new(report_path))?;
+                }
+                return Ok(());
+            }
             
+            // Exports above still run for an empty directory, so cron graphs don't go blank
             if files.is_empty() {
                 println!("No files found in the specified directory.");
                 return Ok(());
             }
             
-            display_storage_report(&files);
+            display_storage_report(&report, scan_matches.is_present("top-per-category"));
+            display_video_summary(&files);
+            
+            if scan_matches.is_present("ages") {
+                display_age_report(&report);
+            }
+            
+            if scan_matches.is_present("tree") {
+                display_directory_tree(dir, &build_directory_tree(dir, &files), depth, min_percent);
+            }
             
             if should_find_duplicates {
-                let duplicates = find_duplicates(&files);
+                if !skipped.is_empty() {
+                    println!(
+                        "\n{} ({} files failed byte verification and were skipped)",
+                        "Paranoid Verification".bold().yellow(),
+                        skipped.len()
+                    );
+                    for (file, reason) in &skipped {
+                        println!("  {} ({})", file.path.display(), reason.red());
+                    }
+                }
                 
                 if duplicates.is_empty() {
                     println!("\n{}", "No duplicate files found.".bold());
@@ -672,6 +6554,68 @@ fn main() -> Result<(), Box<dyn Error>> {
                         println!("\n... and {} more duplicate groups", sorted_duplicates.len() - 5);
                     }
                 }
+                
+                if let Some(report_path) = scan_matches.value_of("report") {
+                    write_duplicate_report(&duplicates, Path::new(report_path))?;
+                    println!("\nDuplicate report written to {}", report_path.cyan());
+                }
+            }
+            
+            if scan_matches.is_present("mismatched") {
+                let mismatches = find_mismatched_extensions(&files);
+                
+                if mismatches.is_empty() {
+                    println!("\n{}", "No extension mismatches found.".bold());
+                } else {
+                    println!(
+                        "\n{} ({} files)",
+                        "Extension Mismatches".bold().yellow(),
+                        mismatches.len()
+                    );
+                    
+                    for mismatch in &mismatches {
+                        println!(
+                            "  {} (.{} but content is {})",
+                            mismatch.path.display(),
+                            mismatch.current_extension.as_deref().unwrap_or("<none>"),
+                            mismatch.detected_extension.cyan()
+                        );
+                    }
+                }
+            }
+            
+            if should_find_similar {
+                let groups = find_similar_images(&files, threshold);
+                
+                if groups.is_empty() {
+                    println!("\n{}", "No similar images found.".bold());
+                } else {
+                    println!(
+                        "\n{} ({} groups, threshold {})",
+                        "Similar Images Found".bold().yellow(),
+                        groups.len(),
+                        threshold
+                    );
+                    
+                    for (i, group) in groups.iter().enumerate() {
+                        println!("\nGroup {} - {} images:", i + 1, group.len());
+                        
+                        for (j, file) in group.iter().enumerate() {
+                            let resolution = file
+                                .dimensions
+                                .map(|(w, h)| format!("{}x{}", w, h))
+                                .unwrap_or_else(|| "?".to_string());
+                            let marker = if j == 0 { "keep".green() } else { "    ".normal() };
+                            println!(
+                                "  {} {} ({}, {})",
+                                marker,
+                                file.path.display(),
+                                resolution,
+                                format_size(file.size).yellow()
+                            );
+                        }
+                    }
+                }
             }
         }
         ("organize", Some(org_matches)) => {
@@ -689,6 +6633,16 @@ fn main() -> Result<(), Box<dyn Error>> {
             let organization_type = org_matches.value_of("by").unwrap();
             let dry_run = org_matches.is_present("dry-run");
             let recursive = org_matches.is_present("recursive");
+            let nested = org_matches.is_present("nested");
+            let template = match org_matches.value_of("template") {
+                Some(template) => Some(template),
+                None if organization_type == "music" => Some(MUSIC_TEMPLATE),
+                None => None,
+            };
+            if let Some(template) = template {
+                parse_template(template)?;
+            }
+            let min_duration = org_matches.value_of("min-duration").map(parse_duration).transpose()?;
             
             println!(
                 "{}",
@@ -702,14 +6656,247 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .green()
             );
             
-            let files = scan_directory(dir, &config, false, recursive)?;
+            let mut read_metadata = organize_metadata(organization_type, template.is_some());
+            // --min-duration needs the video length
+            read_metadata.video |= min_duration.is_some();
+            
+            let options = ScanOptions {
+                recursive,
+                detect_projects: !org_matches.is_present("no-projects"),
+                read_metadata,
+                ..ScanOptions::default()
+            };
+            let mut files = scan_directory(dir, &config, &options)?;
+            
+            if let Some(min_duration) = min_duration {
+                files.retain(|file| meets_min_duration(file, min_duration));
+            }
+            
+            // A music library only takes audio files
+            if organization_type == "music" {
+                files.retain(|file| matches!(file.category, FileCategory::Audio));
+            }
             
             if files.is_empty() {
                 println!("No files found in the specified directory.");
                 return Ok(());
             }
             
-            organize_files(&files, &target_dir, organization_type, dry_run)?;
+            organize_files(&files, &target_dir, organization_type, template, nested, dry_run, &config)?;
+        }
+        ("fix-extensions", Some(fix_matches)) => {
+            if let Some(journal) = fix_matches.value_of("undo") {
+                return undo_journal(Path::new(journal));
+            }
+            
+            let dir = Path::new(fix_matches.value_of("dir").unwrap());
+            let dry_run = fix_matches.is_present("dry-run");
+            let recursive = fix_matches.is_present("recursive");
+            
+            println!(
+                "{}",
+                format!(
+                    "Fixing extensions in {}{}",
+                    dir.display(),
+                    if dry_run { " (DRY RUN)" } else { "" }
+                )
+                .bold()
+                .green()
+            );
+            
+            let options = ScanOptions {
+                recursive,
+                detect_projects: true,
+                ..ScanOptions::default()
+            };
+            let files = scan_directory(dir, &config, &options)?;
+            let mismatches = find_mismatched_extensions(&files);
+            
+            if mismatches.is_empty() {
+                println!("No extension mismatches found.");
+                return Ok(());
+            }
+            
+            fix_extensions(&mismatches, dry_run)?;
+        }
+        ("explain-category", Some(explain_matches)) => {
+            let file = Path::new(explain_matches.value_of("file").unwrap());
+            explain_category(file, &config);
+        }
+        ("diff", Some(diff_matches)) => {
+            let a_dir = Path::new(diff_matches.value_of("a").unwrap());
+            let b_dir = Path::new(diff_matches.value_of("b").unwrap());
+            let json = diff_matches.is_present("json");
+            
+            if !json {
+                println!(
+                    "{}",
+                    format!("Comparing {} with {}", a_dir.display(), b_dir.display()).bold().green()
+                );
+            }
+            
+            let options = ScanOptions {
+                recursive: true,
+                calculate_hashes: true,
+                ..ScanOptions::default()
+            };
+            let (a_files, a_errors) = scan_directory_with_errors(a_dir, &config, &options)?;
+            let (b_files, b_errors) = scan_directory_with_errors(b_dir, &config, &options)?;
+            
+            let mut diff = diff_trees(a_dir, &a_files, b_dir, &b_files);
+            diff.a_errors = a_errors;
+            diff.b_errors = b_errors;
+            
+            // Anything unreadable in A might be missing from B
+            if a_errors > 0 {
+                diff.a_contained_in_b = false;
+            }
+            
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                display_tree_diff(&diff, a_dir, b_dir);
+            }
+            
+            // Exit code tells scripts whether A is fully backed up in B,
+            // or whether A couldn't be read completely
+            if diff.a_errors > 0 {
+                std::process::exit(2);
+            }
+            if !diff.a_contained_in_b {
+                std::process::exit(1);
+            }
+        }
+        ("ui", Some(ui_matches)) => {
+            let dir = Path::new(ui_matches.value_of("dir").unwrap());
+            
+            update_recent_directories(&mut config, dir)?;
+            
+            let options = ScanOptions {
+                recursive: true,
+                calculate_hashes: ui_matches.is_present("duplicates"),
+                detect_projects: !ui_matches.is_present("no-projects"),
+                ..ScanOptions::default()
+            };
+            run_ui(dir, &config, &options)?;
+        }
+        ("compare", Some(compare_matches)) => {
+            if compare_matches.is_present("list") {
+                return list_snapshots();
+            }
+            
+            let depth: usize = compare_matches
+                .value_of("depth")
+                .unwrap()
+                .parse()
+                .map_err(|_| "Depth must be a whole number")?;
+            let top: usize = compare_matches
+                .value_of("top")
+                .unwrap()
+                .parse()
+                .map_err(|_| "Top must be a whole number")?;
+            
+            let a = load_snapshot(compare_matches.value_of("a").unwrap())?;
+            let b = load_snapshot(compare_matches.value_of("b").unwrap())?;
+            
+            // Paths are relative to each root, so different roots rarely line up
+            if a.root != b.root {
+                eprintln!(
+                    "Warning: comparing snapshots of different directories ({} and {})",
+                    a.root.display(),
+                    b.root.display()
+                );
+            }
+            
+            let comparison = compare_snapshots(&a, &b, depth);
+            
+            if compare_matches.is_present("json") {
+                println!("{}", serde_json::to_string_pretty(&comparison)?);
+            } else {
+                display_snapshot_comparison(&comparison, top);
+            }
+        }
+        ("check", Some(check_matches)) => {
+            let dir = Path::new(check_matches.value_of("dir").unwrap());
+            let json = check_matches.is_present("json");
+            
+            let mut rules: Vec<CheckRule> = match check_matches.value_of("rules") {
+                Some(rules_path) => {
+                    let contents = fs::read_to_string(rules_path)?;
+                    serde_json::from_str(&contents).map_err(|e| format!("Invalid rules file {}: {}", rules_path, e))?
+                }
+                None => Vec::new(),
+            };
+            
+            for value in check_matches.values_of("max-size").into_iter().flatten() {
+                let (category, size) = match value.split_once('=') {
+                    Some((category, size)) => (Some(category.to_string()), size),
+                    None => (None, value),
+                };
+                rules.push(CheckRule {
+                    category,
+                    max_total_size: Some(size.to_string()),
+                    ..CheckRule::default()
+                });
+            }
+            if let Some(max_files) = check_matches.value_of("max-files") {
+                rules.push(CheckRule {
+                    max_files: Some(max_files.parse().map_err(|_| "Max files must be a whole number")?),
+                    ..CheckRule::default()
+                });
+            }
+            if let Some(size) = check_matches.value_of("max-file-size") {
+                rules.push(CheckRule { max_file_size: Some(size.to_string()), ..CheckRule::default() });
+            }
+            if let Some(age) = check_matches.value_of("max-age") {
+                rules.push(CheckRule { max_age: Some(age.to_string()), ..CheckRule::default() });
+            }
+            if let Some(size) = check_matches.value_of("max-duplicate-waste") {
+                rules.push(CheckRule { max_duplicate_waste: Some(size.to_string()), ..CheckRule::default() });
+            }
+            
+            if rules.is_empty() {
+                return Err("No rules given (use --rules FILE or limits such as --max-file-size)".into());
+            }
+            
+            // A misspelled category would match nothing and always pass
+            if let Some(category) = rules
+                .iter()
+                .filter_map(|rule| rule.category.as_deref())
+                .find(|category| !known_check_category(category, &config))
+            {
+                return Err(format!("Unknown category '{}' in check rules (use e.g. Videos, Documents or a custom category)", category).into());
+            }
+            
+            // Limits are parsed during evaluation, so catch typos before a long scan
+            evaluate_check_rules(dir, &[], &rules)?;
+            
+            let options = ScanOptions {
+                recursive: true,
+                calculate_hashes: rules.iter().any(|rule| rule.max_duplicate_waste.is_some()),
+                // Policies are about individual files, so look inside projects
+                detect_projects: false,
+                quiet: json,
+                ..ScanOptions::default()
+            };
+            let (files, scan_errors) = scan_directory_with_errors(dir, &config, &options)?;
+            let results = evaluate_check_rules(dir, &files, &rules)?;
+            
+            if json {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            } else {
+                display_check_results(&results, dir);
+            }
+            
+            // Unreadable files may hide violations, so say so even when everything passed
+            if scan_errors > 0 {
+                eprintln!("Warning: {} entries could not be read and were not checked", scan_errors);
+            }
+            
+            // Exit code lets cron and CI jobs fail on violations, apart from errors (1)
+            if results.iter().any(|result| !result.passed) {
+                std::process::exit(2);
+            }
         }
         ("config", Some(config_matches)) => {
             if config_matches.is_present("list") {
@@ -719,12 +6906,37 @@ fn main() -> Result<(), Box<dyn Error>> {
                     println!("  - {}", pattern);
                 }
                 
-                println!("\nCustom categories:");
-                for (category, extensions) in &config.custom_categories {
-                    println!("  - {}: {}", category, extensions.join(", "));
+                println!("\nCustom categories (highest priority first):");
+                let mut rules: Vec<&CategoryRule> = config.category_rules.iter().collect();
+                rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
+                for rule in rules {
+                    let scope = if rule.paths.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" in {}", rule.paths.patterns().join(", "))
+                    };
+                    println!(
+                        "  - {} [priority {}]: {}{}",
+                        rule.name,
+                        rule.priority,
+                        rule.extensions.join(", "),
+                        scope
+                    );
+                }
+                let mut legacy: Vec<_> = config.custom_categories.iter().collect();
+                legacy.sort_by_key(|(name, _)| *name);
+                for (category, extensions) in legacy {
+                    println!("  - {} [priority 0]: {}", category, extensions.join(", "));
+                }
+                
+                println!("\nProject markers: {}", config.project_markers.join(", "));
+                for rule in &config.project_rules {
+                    println!("  - {} projects go to {}", project_kind(&rule.marker), rule.destination);
                 }
                 
                 println!("\nDefault organization method: {}", config.default_organization);
+                println!("Multithreaded hashing threshold: {}", format_size(config.mmap_hash_threshold));
+                println!("Content sniffing mode: {:?}", config.sniff_mode);
                 
                 println!("\nRecent directories:");
                 for dir in &config.recent_directories {
@@ -758,15 +6970,33 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .map(|s| s.trim().to_string())
                         .collect();
                     
+                    let priority = config_matches
+                        .value_of("priority")
+                        .map(|p| p.parse::<i32>())
+                        .transpose()
+                        .map_err(|_| "Priority must be a whole number")?
+                        .unwrap_or(0);
+                    let paths: Vec<String> = config_matches
+                        .values_of("path")
+                        .map(|values| values.map(|v| v.to_string()).collect())
+                        .unwrap_or_default();
+                    
                     if !extensions.is_empty() {
-                        config
-                            .custom_categories
-                            .insert(category.to_string(), extensions.clone());
+                        // Re-adding a category replaces its previous definition
+                        config.custom_categories.remove(category);
+                        config.category_rules.retain(|r| r.name != category);
+                        config.category_rules.push(CategoryRule {
+                            name: category.to_string(),
+                            extensions: extensions.clone(),
+                            priority,
+                            paths: paths.into(),
+                        });
                         save_config(&config)?;
                         println!(
-                            "Added custom category '{}' with extensions: {}",
+                            "Added custom category '{}' with extensions: {} (priority {})",
                             category,
-                            extensions.join(", ")
+                            extensions.join(", "),
+                            priority
                         );
                     } else {
                         println!("No extensions specified for category");
@@ -776,6 +7006,39 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
             }
             
+            if let Some(rule_def) = config_matches.value_of("add-project-rule") {
+                match rule_def.split_once(':') {
+                    Some((marker, destination)) if !marker.is_empty() && !destination.is_empty() => {
+                        config.project_rules.retain(|r| r.marker != marker);
+                        config.project_rules.push(ProjectRule {
+                            marker: marker.to_string(),
+                            destination: destination.to_string(),
+                        });
+                        if !config.project_markers.iter().any(|m| m == marker) {
+                            config.project_markers.insert(0, marker.to_string());
+                        }
+                        save_config(&config)?;
+                        println!("Projects with '{}' will be moved to {}", marker, destination);
+                    }
+                    _ => {
+                        println!("Invalid project rule format. Use 'marker:destination'");
+                    }
+                }
+            }
+            
+            if let Some(category) = config_matches.value_of("remove-category") {
+                let before_len = config.category_rules.len() + config.custom_categories.len();
+                config.category_rules.retain(|r| r.name != category);
+                config.custom_categories.remove(category);
+                
+                if config.category_rules.len() + config.custom_categories.len() < before_len {
+                    save_config(&config)?;
+                    println!("Removed custom category '{}'", category);
+                } else {
+                    println!("Custom category '{}' not found", category);
+                }
+            }
+            
             if let Some(org_method) = config_matches.value_of("set-default-org") {
                 match org_method {
                     "type" | "date" | "ext" => {
@@ -788,12 +7051,54 @@ fn main() -> Result<(), Box<dyn Error>> {
                     }
                 }
             }
+            
+            if let Some(mode) = config_matches.value_of("set-sniff-mode") {
+                let sniff_mode = match mode {
+                    "off" => Some(SniffMode::Off),
+                    "fallback" => Some(SniffMode::Fallback),
+                    "primary" => Some(SniffMode::Primary),
+                    _ => None,
+                };
+                
+                match sniff_mode {
+                    Some(sniff_mode) => {
+                        config.sniff_mode = sniff_mode;
+                        save_config(&config)?;
+                        println!("Content sniffing mode set to '{}'", mode);
+                    }
+                    None => {
+                        println!("Invalid sniffing mode. Use 'off', 'fallback', or 'primary'");
+                    }
+                }
+            }
+            
+            if let Some(threshold) = config_matches.value_of("set-mmap-threshold") {
+                match threshold.parse::<u64>().ok().and_then(|megabytes| megabytes.checked_mul(1024 * 1024)) {
+                    Some(bytes) => {
+                        config.mmap_hash_threshold = bytes;
+                        save_config(&config)?;
+                        println!(
+                            "Files of {} or more will be hashed with multiple threads",
+                            format_size(config.mmap_hash_threshold)
+                        );
+                    }
+                    None => {
+                        return Err("Invalid threshold. Specify a size in megabytes".into());
+                    }
+                }
+            }
         }
         _ => {
             println!("{}", "TidyFS - Smart File System Organizer".bold().green());
             println!("Run with a subcommand to begin:");
             println!("  {} - Scan directory and show statistics", "scan".cyan());
             println!("  {} - Organize files into folders", "organize".cyan());
+            println!("  {} - Rename files to match their content", "fix-extensions".cyan());
+            println!("  {} - Compare two directory trees", "diff".cyan());
+            println!("  {} - Compare two saved scan snapshots", "compare".cyan());
+            println!("  {} - Browse scan results in a terminal UI", "ui".cyan());
+            println!("  {} - Check a directory against size, age and duplicate limits", "check".cyan());
+            println!("  {} - Show which category definition matches a file", "explain-category".cyan());
             println!("  {} - Configure TidyFS settings", "config".cyan());
             println!("\nUse --help with any subcommand for more information.");
         }
@@ -17,53 +17,161 @@ mod tests {
         path
     }
 
+    // Helper function to categorize by extension alone, as classify_file does before sniffing
+    fn determine_category(path: &Path, config: &TidyConfig) -> (FileCategory, Option<String>) {
+        match category_matches(path, config).first() {
+            Some(found) => found.category(),
+            None => unmatched_category(path),
+        }
+    }
+
+    // Helper function to build a scanned file; override fields with struct update syntax
+    fn test_file(path: &str, size: u64) -> FileInfo {
+        FileInfo {
+            path: PathBuf::from(path),
+            size,
+            last_modified: 12345,
+            category: FileCategory::Document,
+            subcategory: None,
+            category_source: CategorySource::Extension,
+            content: None,
+            hash: None,
+            perceptual_hash: None,
+            dimensions: None,
+            exif: None,
+            audio: None,
+            video: None,
+            document: None,
+        }
+    }
+
     #[test]
     fn test_determine_category() {
         let config = TidyConfig::default();
         
         // Test document types
         assert!(matches!(
-            determine_category(&Path::new("test.pdf"), &config),
+            determine_category(&Path::new("test.pdf"), &config).0,
             FileCategory::Document
         ));
         assert!(matches!(
-            determine_category(&Path::new("test.docx"), &config),
+            determine_category(&Path::new("test.docx"), &config).0,
             FileCategory::Document
         ));
         
         // Test image types
         assert!(matches!(
-            determine_category(&Path::new("test.jpg"), &config),
+            determine_category(&Path::new("test.jpg"), &config).0,
             FileCategory::Image
         ));
         assert!(matches!(
-            determine_category(&Path::new("test.png"), &config),
+            determine_category(&Path::new("test.png"), &config).0,
             FileCategory::Image
         ));
         
         // Test code types
         assert!(matches!(
-            determine_category(&Path::new("test.rs"), &config),
+            determine_category(&Path::new("test.rs"), &config).0,
             FileCategory::Code
         ));
         assert!(matches!(
-            determine_category(&Path::new("test.py"), &config),
+            determine_category(&Path::new("test.py"), &config).0,
             FileCategory::Code
         ));
         
         // Test unknown type
         assert!(matches!(
-            determine_category(&Path::new("test.xyz"), &config),
+            determine_category(&Path::new("test.xyz"), &config).0,
             FileCategory::Other(ext) if ext == "xyz"
         ));
         
         // Test no extension
         assert!(matches!(
-            determine_category(&Path::new("test"), &config),
+            determine_category(&Path::new("test"), &config).0,
             FileCategory::Other(ext) if ext == "unknown"
         ));
     }
 
+    #[test]
+    fn test_category_database() {
+        let config = TidyConfig::default();
+        
+        let (category, subcategory) = determine_category(&Path::new("budget.xlsx"), &config);
+        assert!(matches!(category, FileCategory::Document));
+        assert_eq!(subcategory.as_deref(), Some("Spreadsheet"));
+        
+        let (category, subcategory) = determine_category(&Path::new("IMG_0001.CR2"), &config);
+        assert!(matches!(category, FileCategory::Image));
+        assert_eq!(subcategory.as_deref(), Some("Raw"));
+        
+        assert!(matches!(determine_category(&Path::new("font.woff2"), &config).0, FileCategory::Font));
+        assert!(matches!(determine_category(&Path::new("ubuntu.iso"), &config).0, FileCategory::DiskImage));
+        assert!(matches!(determine_category(&Path::new("part.stl"), &config).0, FileCategory::Model3D));
+        assert!(matches!(determine_category(&Path::new("movie.srt"), &config).0, FileCategory::Subtitle));
+        assert!(matches!(determine_category(&Path::new("app.sqlite"), &config).0, FileCategory::Database));
+        
+        // Folders nest only when a subcategory is requested
+        let index = category_index();
+        assert_eq!(index.folder(&FileCategory::Document, None).unwrap(), "Documents");
+        assert_eq!(
+            index.folder(&FileCategory::Document, Some("Spreadsheet")).unwrap(),
+            "Documents/Spreadsheets"
+        );
+        assert_eq!(index.folder(&FileCategory::Font, None).unwrap(), "Fonts");
+        
+        // Later databases override earlier definitions
+        let mut index = CategoryIndex::default();
+        index.add(&serde_json::from_str(BUILTIN_CATEGORIES).unwrap());
+        index.add(&serde_json::from_str(r#"{"categories": [{"name": "Data", "folder": "Data", "extensions": ["csv"]}]}"#).unwrap());
+        assert!(matches!(index.lookup("csv").unwrap().0, FileCategory::Other(name) if name == "Data"));
+        assert!(matches!(index.lookup("xlsx").unwrap().0, FileCategory::Document));
+    }
+
+    #[test]
+    fn test_compound_extensions() {
+        assert_eq!(
+            split_extension(Path::new("dir/backup.tar.gz")),
+            ("backup".to_string(), Some("tar.gz".to_string()))
+        );
+        assert_eq!(
+            split_extension(Path::new("Backup.TAR.GZ")),
+            ("Backup".to_string(), Some("TAR.GZ".to_string()))
+        );
+        assert_eq!(
+            split_extension(Path::new("linux-6.1-1.pkg.tar.zst")),
+            ("linux-6.1-1".to_string(), Some("pkg.tar.zst".to_string()))
+        );
+        assert_eq!(
+            split_extension(Path::new("my.notes.txt")),
+            ("my.notes".to_string(), Some("txt".to_string()))
+        );
+        assert_eq!(split_extension(Path::new(".bashrc")), (".bashrc".to_string(), None));
+        assert_eq!(split_extension(Path::new("tar.gz")), ("tar".to_string(), Some("gz".to_string())));
+        // Lowercasing the Kelvin sign shortens the name, which must not shift the split
+        assert_eq!(
+            split_extension(Path::new("p\u{212A}g.tar.zst")),
+            ("p\u{212A}g".to_string(), Some("tar.zst".to_string()))
+        );
+        assert_eq!(
+            split_extension(Path::new("\u{212A}.pkg.tar.zst")),
+            ("\u{212A}".to_string(), Some("pkg.tar.zst".to_string()))
+        );
+        
+        let config = TidyConfig::default();
+        assert!(matches!(
+            determine_category(&Path::new("backup.tar.gz"), &config).0,
+            FileCategory::Archive
+        ));
+        assert!(matches!(
+            determine_category(&Path::new("script.user.js"), &config).0,
+            FileCategory::Code
+        ));
+        assert!(matches!(
+            determine_category(&Path::new("types.d.ts"), &config).0,
+            FileCategory::Other(ext) if ext == "d.ts"
+        ));
+    }
+
     #[test]
     fn test_determine_category_with_custom_config() {
         let mut config = TidyConfig::default();
@@ -78,21 +186,246 @@ mod tests {
         
         // Test standard category still works
         assert!(matches!(
-            determine_category(&Path::new("test.jpg"), &config),
+            determine_category(&Path::new("test.jpg"), &config).0,
             FileCategory::Image
         ));
         
         // Test custom category
         assert!(matches!(
-            determine_category(&Path::new("test.abc"), &config),
+            determine_category(&Path::new("test.abc"), &config).0,
             FileCategory::Other(cat) if cat == "CustomCategory"
         ));
         assert!(matches!(
-            determine_category(&Path::new("test.xyz"), &config),
+            determine_category(&Path::new("test.xyz"), &config).0,
             FileCategory::Other(cat) if cat == "CustomCategory"
         ));
     }
 
+    #[test]
+    fn test_sniff_file_type() {
+        let dir = tempdir().unwrap();
+        
+        // Magic bytes decide regardless of the name
+        let png = dir.path().join("upload.bin");
+        fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let pdf = dir.path().join("download");
+        fs::write(&pdf, b"%PDF-1.7\n").unwrap();
+        let elf = dir.path().join("tool");
+        fs::write(&elf, b"\x7fELF\x02\x01\x01").unwrap();
+        let text = dir.path().join("notes");
+        fs::write(&text, b"just some words").unwrap();
+        
+        assert_eq!(sniff_file_type(&png).unwrap().extension, "png");
+        assert_eq!(sniff_file_type(&pdf).unwrap().extension, "pdf");
+        assert!(matches!(sniff_file_type(&elf).unwrap().category, FileCategory::Executable));
+        assert!(sniff_file_type(&text).is_none());
+        
+        // Fallback mode only sniffs when the extension doesn't classify the file
+        let mut config = TidyConfig::default();
+        let classification = classify_file(&pdf, &config);
+        assert!(matches!(classification.category, FileCategory::Document));
+        assert_eq!(classification.source, CategorySource::Content);
+        
+        let classification = classify_file(&png, &config);
+        assert!(matches!(classification.category, FileCategory::Image));
+        assert_eq!(classification.source, CategorySource::Content);
+        
+        let misnamed = dir.path().join("photo.txt");
+        fs::write(&misnamed, b"\xff\xd8\xff\xe0").unwrap();
+        let classification = classify_file(&misnamed, &config);
+        assert!(matches!(classification.category, FileCategory::Document));
+        assert_eq!(classification.source, CategorySource::Extension);
+        
+        // Primary mode trusts the content over the extension
+        config.sniff_mode = SniffMode::Primary;
+        let classification = classify_file(&misnamed, &config);
+        assert!(matches!(classification.category, FileCategory::Image));
+        assert_eq!(classification.source, CategorySource::Content);
+        
+        // Off never reads the file
+        config.sniff_mode = SniffMode::Off;
+        let classification = classify_file(&pdf, &config);
+        assert!(matches!(classification.category, FileCategory::Other(ext) if ext == "unknown"));
+    }
+
+    #[test]
+    fn test_classify_text() {
+        assert_eq!(
+            classify_text(b"line one\nline two\n"),
+            ContentKind::Text { encoding: TextEncoding::Ascii, line_ending: LineEnding::Lf }
+        );
+        assert_eq!(
+            classify_text("caf\u{e9}\r\nna\u{ef}ve\r\n".as_bytes()),
+            ContentKind::Text { encoding: TextEncoding::Utf8, line_ending: LineEnding::Crlf }
+        );
+        assert_eq!(
+            classify_text(b"\xEF\xBB\xBFkey=value"),
+            ContentKind::Text { encoding: TextEncoding::Utf8Bom, line_ending: LineEnding::None }
+        );
+        assert_eq!(
+            classify_text(b"\xFF\xFEh\0i\0\r\0\n\0"),
+            ContentKind::Text { encoding: TextEncoding::Utf16Le, line_ending: LineEnding::Crlf }
+        );
+        assert_eq!(
+            classify_text(b"caf\xe9\nok\r"),
+            ContentKind::Text { encoding: TextEncoding::Legacy8Bit, line_ending: LineEnding::Mixed }
+        );
+        assert_eq!(classify_text(b"\x00\x01\x02\x03binary"), ContentKind::Binary);
+        
+        // Unknown extensions holding text land in the Text category
+        let dir = tempdir().unwrap();
+        let log = create_test_file(&dir.path(), "server.log.1", "started\nstopped\n");
+        let blob = dir.path().join("data.out2");
+        fs::write(&blob, [0u8, 159, 146, 150, 0, 1]).unwrap();
+        
+        let config = TidyConfig::default();
+        let classification = classify_file(&log, &config);
+        assert!(matches!(classification.category, FileCategory::Text));
+        assert_eq!(classification.source, CategorySource::Content);
+        
+        let classification = classify_file(&blob, &config);
+        assert!(matches!(classification.category, FileCategory::Other(ext) if ext == "out2"));
+        assert_eq!(classification.content, Some(ContentKind::Binary));
+    }
+
+    #[test]
+    fn test_find_extension_mismatch() {
+        let dir = tempdir().unwrap();
+        
+        let jpeg_as_png = dir.path().join("photo.png");
+        fs::write(&jpeg_as_png, b"\xff\xd8\xff\xe0").unwrap();
+        let jpeg = dir.path().join("photo.jpeg");
+        fs::write(&jpeg, b"\xff\xd8\xff\xe0").unwrap();
+        let pdf_no_ext = dir.path().join("download");
+        fs::write(&pdf_no_ext, b"%PDF-1.4").unwrap();
+        let script = dir.path().join("run.py");
+        fs::write(&script, b"#!/usr/bin/env python3").unwrap();
+        
+        let mismatch = find_extension_mismatch(&jpeg_as_png).unwrap();
+        assert_eq!(mismatch.current_extension.as_deref(), Some("png"));
+        assert_eq!(mismatch.detected_extension, "jpg");
+        
+        // Equivalent spellings are not mismatches
+        assert!(find_extension_mismatch(&jpeg).is_none());
+        
+        let mismatch = find_extension_mismatch(&pdf_no_ext).unwrap();
+        assert_eq!(mismatch.current_extension, None);
+        assert_eq!(mismatch.detected_extension, "pdf");
+        
+        // Scripts have no single correct suffix
+        assert!(find_extension_mismatch(&script).is_none());
+        
+        // Weak signatures need a structural check or a missing extension
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, b"BMW service notes\nMZ-80 manual\n").unwrap();
+        assert!(find_extension_mismatch(&notes).is_none());
+        let csv = dir.path().join("export.csv");
+        fs::write(&csv, b"\xff\xfb\x90\x44").unwrap();
+        assert!(find_extension_mismatch(&csv).is_none());
+        let frame = dir.path().join("track");
+        fs::write(&frame, b"\xff\xfb\x90\x44").unwrap();
+        assert_eq!(find_extension_mismatch(&frame).unwrap().detected_extension, "mp3");
+        
+        let mut bmp = b"BM\x36\0\0\0\0\0\0\0\x36\0\0\0\x28\0\0\0".to_vec();
+        bmp.resize(54, 0);
+        let bmp_as_jpg = dir.path().join("image.jpg");
+        fs::write(&bmp_as_jpg, &bmp).unwrap();
+        assert_eq!(find_extension_mismatch(&bmp_as_jpg).unwrap().detected_extension, "bmp");
+        
+        let mut pe = vec![0u8; 0x84];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3C] = 0x80;
+        pe[0x80..].copy_from_slice(b"PE\0\0");
+        let pe_as_pdf = dir.path().join("invoice.pdf");
+        fs::write(&pe_as_pdf, &pe).unwrap();
+        assert_eq!(find_extension_mismatch(&pe_as_pdf).unwrap().detected_extension, "exe");
+        
+        // The MP4 family shares one container, so brands don't need to match the suffix
+        let audiobook = dir.path().join("book.m4b");
+        fs::write(&audiobook, b"\0\0\0\x20ftypisom\0\0\x02\0").unwrap();
+        assert!(find_extension_mismatch(&audiobook).is_none());
+        let clip = dir.path().join("clip.mov");
+        fs::write(&clip, b"\0\0\0\x20ftypmp42\0\0\0\0").unwrap();
+        assert!(find_extension_mismatch(&clip).is_none());
+        
+        // Formats built on another container keep the suffix the category database gives them
+        let cr3 = dir.path().join("IMG_0001.CR3");
+        fs::write(&cr3, b"\0\0\0\x18ftypcrx \0\0\0\x01crx isom").unwrap();
+        assert!(find_extension_mismatch(&cr3).is_none());
+        let pef = dir.path().join("IMGP0001.pef");
+        fs::write(&pef, b"II*\0\x08\0\0\0").unwrap();
+        assert!(find_extension_mismatch(&pef).is_none());
+        let key = dir.path().join("slides.key");
+        fs::write(&key, b"PK\x03\x04\x14\0\0\0\x08\0").unwrap();
+        assert!(find_extension_mismatch(&key).is_none());
+        
+        // ...but not a suffix that has a signature of its own
+        let zip_as_pdf = dir.path().join("report.pdf");
+        fs::write(&zip_as_pdf, b"PK\x03\x04\x14\0\0\0\x08\0").unwrap();
+        assert_eq!(find_extension_mismatch(&zip_as_pdf).unwrap().detected_extension, "zip");
+    }
+
+    #[test]
+    fn test_category_rule_priority() {
+        let dir = tempdir().unwrap();
+        let datasets = dir.path().join("datasets");
+        fs::create_dir(&datasets).unwrap();
+        
+        let mut config = TidyConfig::default();
+        config.category_rules = vec![
+            CategoryRule {
+                name: "Config".to_string(),
+                extensions: vec!["json".to_string()],
+                priority: 0,
+                paths: PathScope::default(),
+            },
+            CategoryRule {
+                name: "Data".to_string(),
+                extensions: vec!["json".to_string()],
+                priority: 10,
+                paths: vec![datasets.to_string_lossy().to_string()].into(),
+            },
+            CategoryRule {
+                name: "Fallback".to_string(),
+                extensions: vec!["pdf".to_string(), "json".to_string()],
+                priority: -5,
+                paths: PathScope::default(),
+            },
+        ];
+        
+        // Path-scoped rule with the higher priority wins inside its scope
+        assert!(matches!(
+            determine_category(&datasets.join("train.json"), &config).0,
+            FileCategory::Other(name) if name == "Data"
+        ));
+        assert!(matches!(
+            determine_category(&dir.path().join("settings.json"), &config).0,
+            FileCategory::Other(name) if name == "Config"
+        ));
+        
+        // Negative priority only applies below the built-in database
+        assert!(matches!(
+            determine_category(&Path::new("paper.pdf"), &config).0,
+            FileCategory::Document
+        ));
+        
+        // Every matching definition is reported, best first
+        let matches = category_matches(&datasets.join("train.json"), &config);
+        let names: Vec<String> = matches.iter().map(|m| m.category().0.name().to_string()).collect();
+        assert_eq!(names, vec!["Data", "Config", "Fallback"]);
+        
+        // Legacy categories sharing an extension resolve by name, every time
+        let mut config = TidyConfig::default();
+        config.custom_categories.insert("Zeta".to_string(), vec!["abc".to_string()]);
+        config.custom_categories.insert("Alpha".to_string(), vec!["abc".to_string()]);
+        for _ in 0..10 {
+            assert!(matches!(
+                determine_category(&Path::new("file.abc"), &config).0,
+                FileCategory::Other(name) if name == "Alpha"
+            ));
+        }
+    }
+
     #[test]
     fn test_format_size() {
         assert_eq!(format_size(500), "500 bytes");
@@ -123,36 +456,28 @@ mod tests {
         
         // Different files should have different hashes
         assert_ne!(hash1, hash3);
+        
+        // The mmap path must produce the same hash as the streaming path
+        assert_eq!(calculate_hash_mmap(&file1).unwrap(), hash1);
+        assert_eq!(calculate_hash_mmap(&file3).unwrap(), hash3);
     }
 
     #[test]
     fn test_find_duplicates() {
         // Create some file info objects with hashes
-        let file1 = FileInfo {
-            path: PathBuf::from("file1.txt"),
-            size: 100,
-            last_modified: 12345,
-            category: FileCategory::Document,
-            hash: Some("hash1".to_string()),
-        };
-        
+        let file1 = FileInfo { hash: Some("hash1".to_string()), ..test_file("file1.txt", 100) };
         let file2 = FileInfo {
-            path: PathBuf::from("file2.txt"),
-            size: 100,
             last_modified: 12346,
-            category: FileCategory::Document,
             hash: Some("hash1".to_string()),  // Same hash as file1
+            ..test_file("file2.txt", 100)
         };
-        
         let file3 = FileInfo {
-            path: PathBuf::from("file3.txt"),
-            size: 200,
             last_modified: 12347,
-            category: FileCategory::Document,
             hash: Some("hash2".to_string()),  // Different hash
+            ..test_file("file3.txt", 200)
         };
         
-        let files = vec![file1, file2, file3];
+        let files = vec![file2, file1, file3];
         
         // Find duplicates
         let duplicates = find_duplicates(&files);
@@ -169,10 +494,831 @@ mod tests {
             .map(|f| f.path.to_string_lossy().to_string())
             .collect();
         
-        assert!(duplicate_paths.contains(&"file1.txt".to_string()));
-        assert!(duplicate_paths.contains(&"file2.txt".to_string()));
+        // Sorted by path regardless of scan order
+        assert_eq!(duplicate_paths, vec!["file1.txt".to_string(), "file2.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_build_storage_report() {
+        let file = |path: &str, size: u64, category: FileCategory, hash: &str| FileInfo {
+            category,
+            hash: Some(hash.to_string()),
+            ..test_file(path, size)
+        };
+        
+        let files = vec![
+            file("a.txt", 100, FileCategory::Document, "hash1"),
+            file("b.txt", 100, FileCategory::Document, "hash1"),
+            file("c.mp4", 600, FileCategory::Video, "hash2"),
+            file("d.xyz", 200, FileCategory::Other("xyz".to_string()), "hash3"),
+        ];
+        
+        let duplicates = find_duplicates(&files);
+        let report = build_storage_report(Path::new(""), &files, &ReportOptions::default(), Some(&duplicates));
+        
+        assert_eq!(report.total_files, 4);
+        assert_eq!(report.total_size, 1000);
+        
+        let categories: Vec<(&str, u64, usize)> = report
+            .categories
+            .iter()
+            .map(|c| (c.category.as_str(), c.size, c.files))
+            .collect();
+        assert_eq!(categories, vec![("Videos", 600, 1), ("Documents", 200, 2), ("Other (.xyz)", 200, 1)]);
+        assert_eq!(report.categories[0].percent, 60.0);
+        
+        assert_eq!(report.largest_files[0].path, PathBuf::from("c.mp4"));
+        assert_eq!(report.largest_files[1].category, "Other (.xyz)");
+        assert_eq!(report.duplicate_groups.len(), 1);
+        assert_eq!(report.duplicate_groups[0].wasted_bytes, 100);
+        
+        // NDJSON records carry their type alongside the flattened fields
+        let line = serde_json::to_value(ScanRecord::Category(&report.categories[0])).unwrap();
+        assert_eq!(line["type"], "category");
+        assert_eq!(line["files"], 1);
+        let line = serde_json::to_value(ScanRecord::LargestFile(&report.largest_files[0])).unwrap();
+        assert_eq!(line["last_modified"], 12345);
+        let skipped = SkippedDuplicate { path: PathBuf::from("b.txt"), reason: "changed since scan".to_string() };
+        let line = serde_json::to_value(ScanRecord::SkippedDuplicate(&skipped)).unwrap();
+        assert_eq!(line["type"], "skipped_duplicate");
+        assert_eq!(line["reason"], "changed since scan");
+    }
+
+    #[test]
+    fn test_directory_tree_lines() {
+        let files = vec![
+            test_file("r/big/a.bin", 600),
+            test_file("r/big/deep/b.bin", 200),
+            test_file("r/top.txt", 185),
+            test_file("r/small/c.bin", 10),
+            test_file("r/tiny/d.bin", 5),
+        ];
+        let tree = build_directory_tree(Path::new("r"), &files);
+        let lines = |max_depth, min_percent| {
+            let mut out = Vec::new();
+            tree_child_lines(&tree, "", 1, max_depth, tree.size, min_percent, &mut out);
+            out
+        };
+        
+        // Depth 1 stops at the top level; entries under 5% fold into one line
+        let shallow = lines(1, 5.0);
+        assert_eq!(shallow.len(), 3);
+        assert!(shallow[0].starts_with("├── big/"));
+        assert!(shallow[1].starts_with("├── (files)"));
+        assert!(shallow[2].contains("└── (2 smaller entries)"));
+        assert!(shallow[2].ends_with(" 2"));
+        
+        // One level deeper shows big/'s own files and subdirectory, but nothing below
+        let deeper = lines(2, 5.0);
+        assert_eq!(deeper.len(), 5);
+        assert!(deeper[1].starts_with("│   ├── (files)"));
+        assert!(deeper[2].starts_with("│   └── deep/"));
+        
+        // Without a cut-off every directory gets its own line
+        let all = lines(1, 0.0);
+        assert_eq!(all.len(), 4);
+        assert!(all.iter().any(|line| line.starts_with("└── tiny/")));
+    }
+
+    #[test]
+    fn test_age_report() {
+        let file = |path: &str, size: u64, category: FileCategory, age_days: u64| FileInfo {
+            last_modified: unix_now() - age_days * 86_400,
+            category,
+            ..test_file(path, size)
+        };
+        
+        let files = vec![
+            file("a.txt", 100, FileCategory::Document, 1),
+            file("b.txt", 100, FileCategory::Document, 2),
+            file("c.mp4", 600, FileCategory::Video, 4 * 365),
+            file("d.mp4", 50, FileCategory::Video, 60),
+        ];
+        let report = build_storage_report(Path::new(""), &files, &ReportOptions::default(), None);
+        
+        let overall: Vec<usize> = report.age_buckets.iter().map(|b| b.files).collect();
+        assert_eq!(overall, vec![2, 0, 1, 0, 0, 1]);
+        assert_eq!(report.category_ages[0].category, "Videos");
+        assert_eq!(report.category_ages[0].buckets[5].size, 600);
+        assert_eq!(report.category_ages[1].buckets[0].files, 2);
+        assert_eq!(report.stale_files.len(), 1);
+        assert_eq!(report.stale_files[0].path, PathBuf::from("c.mp4"));
+        
+        // Absurd thresholds clamp to the epoch instead of overflowing
+        assert_eq!(stale_cutoff(u64::MAX), 0);
+        let options = ReportOptions { stale_years: u64::MAX, ..ReportOptions::default() };
+        assert!(build_storage_report(Path::new(""), &files, &options, None).stale_files.is_empty());
+    }
+
+    #[test]
+    fn test_largest_directories_and_top_lists() {
+        let files = vec![
+            test_file("docs/a.txt", 100),
+            test_file("docs/b.txt", 100),
+            FileInfo { category: FileCategory::Video, ..test_file("media/clips/c.mp4", 600) },
+            test_file("d.txt", 200),
+        ];
+        let report = build_storage_report(Path::new(""), &files, &ReportOptions::default(), None);
+        
+        let directories: Vec<(PathBuf, u64)> = report
+            .largest_directories
+            .iter()
+            .map(|d| (d.path.clone(), d.size))
+            .collect();
+        // Only top-level directories, so media/clips doesn't repeat media
+        assert_eq!(directories, vec![(PathBuf::from("media"), 600), (PathBuf::from("docs"), 200)]);
+        assert_eq!(report.category_top_files[1].category, "Documents");
+        assert_eq!(report.category_top_files[1].files.len(), 3);
+        
+        // --top shortens every list
+        let options = ReportOptions { top: 1, ..ReportOptions::default() };
+        let report = build_storage_report(Path::new(""), &files, &options, None);
+        assert_eq!(report.largest_files.len(), 1);
+        assert_eq!(report.largest_directories.len(), 1);
+        assert!(report.category_top_files.iter().all(|top| top.files.len() == 1));
+        
+        // The stale list keeps its own, longer default
+        let old: Vec<FileInfo> = (0..12).map(|i| test_file(&format!("old{}.txt", i), i)).collect();
+        let report = build_storage_report(Path::new(""), &old, &ReportOptions::default(), None);
+        assert_eq!(report.largest_files.len(), 5);
+        assert_eq!(report.stale_files.len(), 10);
+    }
+
+    #[test]
+    fn test_write_prometheus_metrics() {
+        let file = |path: &str, size: u64, category: FileCategory, hash: &str| FileInfo {
+            last_modified: unix_now(),
+            category,
+            hash: Some(hash.to_string()),
+            ..test_file(path, size)
+        };
+        let mut files = vec![
+            file("a.txt", 100, FileCategory::Document, "hash1"),
+            file("b.txt", 100, FileCategory::Document, "hash1"),
+            file("c.mp4", 600, FileCategory::Video, "hash2"),
+            file("d.xyz", 200, FileCategory::Other("xyz".to_string()), "hash3"),
+        ];
+        files[2].last_modified = 12345;
+        
+        // Written through a temporary file next to the target
+        let dir = tempdir().unwrap();
+        let prom_path = dir.path().join("tidyfs.prom");
+        let root = dir.path().join("say \"hi\"");
+        let duplicates = find_duplicates(&files);
+        let report = build_storage_report(Path::new(""), &files, &ReportOptions::default(), Some(&duplicates));
+        let stats = ScanRunStats { duration_secs: 1.5, errors: 2, duplicates_checked: true };
+        write_prometheus_metrics(&prom_path, &root, &files, &report, &stats).unwrap();
+        
+        let metrics = fs::read_to_string(&prom_path).unwrap();
+        let labels = format!("root=\"{}\"", root.display().to_string().replace('"', "\\\""));
+        assert!(metrics.contains("# TYPE tidyfs_bytes gauge\n"));
+        assert!(metrics.contains(&format!("tidyfs_bytes{{{}}} 1000\n", labels)));
+        assert!(metrics.contains(&format!("tidyfs_category_bytes{{{},category=\"Other (.xyz)\"}} 200\n", labels)));
+        assert!(metrics.contains(&format!("tidyfs_duplicate_wasted_bytes{{{}}} 100\n", labels)));
+        assert!(metrics.contains(&format!("tidyfs_stale_bytes{{{}}} 600\n", labels)));
+        assert!(metrics.contains(&format!("tidyfs_scan_errors{{{}}} 2\n", labels)));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
     }
 
+    #[test]
+    fn test_verify_duplicates() {
+        let dir = tempdir().unwrap();
+        
+        let keeper = create_test_file(&dir.path(), "keeper.txt", "same content");
+        let copy = create_test_file(&dir.path(), "later-copy.txt", "same content");
+        let changed = create_test_file(&dir.path(), "later-changed.txt", "same content");
+        
+        let config = TidyConfig::default();
+        let infos: Vec<FileInfo> = [&keeper, &copy, &changed]
+            .iter()
+            .map(|p| get_file_info(p, &config, &ScanOptions { calculate_hashes: true, ..ScanOptions::default() }).unwrap())
+            .collect();
+        
+        // Modify one file after the scan (different size, so always detected)
+        create_test_file(&dir.path(), "later-changed.txt", "other content!");
+        
+        let (confirmed, skipped) = verify_duplicates(find_duplicates(&infos));
+        
+        assert_eq!(confirmed.len(), 1);
+        let group = confirmed.values().next().unwrap();
+        assert_eq!(group.len(), 2);
+        assert_eq!(group[0].path, keeper);
+        
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0.path, changed);
+        
+        assert!(files_identical(&keeper, &copy).unwrap());
+        assert!(!files_identical(&keeper, &changed).unwrap());
+    }
+
+    #[test]
+    fn test_write_duplicate_report() {
+        let dir = tempdir().unwrap();
+        
+        let file = |path: &str| FileInfo { hash: Some("hash1".to_string()), ..test_file(path, 100) };
+        let files = vec![file("keep.txt"), file("the copy, it's.txt"), file("x\nrm -rf ~.txt")];
+        let duplicates = find_duplicates(&files);
+        
+        let report = build_duplicate_report(&duplicates);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].wasted_bytes, 200);
+        
+        let csv_path = dir.path().join("dupes.csv");
+        write_duplicate_report(&duplicates, &csv_path).unwrap();
+        let csv = fs::read_to_string(&csv_path).unwrap();
+        assert!(csv.contains("1,hash1,100,200,12345,\"the copy, it's.txt\""));
+        
+        let sh_path = dir.path().join("dupes.sh");
+        write_duplicate_report(&duplicates, &sh_path).unwrap();
+        let script = fs::read_to_string(&sh_path).unwrap();
+        assert!(script.contains("# keep 'keep.txt'"));
+        assert!(script.contains("# rm -- 'the copy, it'\\''s.txt'"));
+        
+        // Names with newlines are left out rather than breaking out of the comment
+        assert!(script.contains("# skipped x\\nrm -rf ~.txt, name contains control characters\n"));
+        assert!(!script.lines().any(|line| line.starts_with("rm")));
+        
+        assert!(write_duplicate_report(&duplicates, &dir.path().join("dupes.txt")).is_err());
+        
+        // The keeper is the first path in sorted order, however the group was ordered
+        let (b, a) = (file("b.txt"), file("a.txt"));
+        let unsorted: DuplicateGroups = HashMap::from([("hash1".to_string(), vec![&b, &a])]);
+        let report = build_duplicate_report(&unsorted);
+        assert_eq!(report[0].files[0].path, PathBuf::from("a.txt"));
+        write_duplicate_report(&unsorted, &sh_path).unwrap();
+        let script = fs::read_to_string(&sh_path).unwrap();
+        assert!(script.contains("# keep 'a.txt'\n# rm -- 'b.txt'\n"));
+    }
+
+    #[test]
+    fn test_find_similar_images() {
+        let image = |name: &str, phash: u64, dims: (u32, u32), size: u64| FileInfo {
+            category: FileCategory::Image,
+            perceptual_hash: Some(phash),
+            dimensions: Some(dims),
+            ..test_file(name, size)
+        };
+        
+        let files = vec![
+            image("small.jpg", 0b1011_0000, (640, 480), 50_000),
+            image("large.jpg", 0b1011_0001, (4000, 3000), 3_000_000),
+            image("other.jpg", !0b1011_0000, (640, 480), 60_000),
+        ];
+        
+        assert_eq!(hamming_distance(0b1011_0000, 0b1011_0001), 1);
+        
+        // Only the two near-identical hashes should be grouped
+        let groups = find_similar_images(&files, 5);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+        
+        // Highest resolution copy comes first as the one to keep
+        assert_eq!(groups[0][0].path, PathBuf::from("large.jpg"));
+        
+        // A zero threshold only matches identical hashes
+        assert!(find_similar_images(&files, 0).is_empty());
+    }
+
+    #[test]
+    fn test_diff_trees() {
+        let file = |path: &str, hash: &str| FileInfo { hash: Some(hash.to_string()), ..test_file(path, 10) };
+        
+        let a_files = vec![
+            file("a/same.txt", "h1"),
+            file("a/old/moved.txt", "h2"),
+            file("a/changed.txt", "h3"),
+            file("a/only.txt", "h4"),
+        ];
+        let b_files = vec![
+            file("b/same.txt", "h1"),
+            file("b/new/moved.txt", "h2"),
+            file("b/changed.txt", "h5"),
+            file("b/extra.txt", "h6"),
+        ];
+        
+        let diff = diff_trees(Path::new("a"), &a_files, Path::new("b"), &b_files);
+        
+        assert_eq!(diff.identical, 1);
+        assert_eq!(diff.only_in_a, vec![PathBuf::from("only.txt")]);
+        assert_eq!(diff.only_in_b, vec![PathBuf::from("extra.txt")]);
+        assert_eq!(diff.modified, vec![PathBuf::from("changed.txt")]);
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(diff.moved[0].b_path, PathBuf::from("new/moved.txt"));
+        assert!(!diff.a_contained_in_b);
+        
+        // A subset of B is fully contained
+        let diff = diff_trees(Path::new("a"), &a_files[..2], Path::new("b"), &b_files);
+        assert!(diff.a_contained_in_b);
+    }
+
+    #[test]
+    fn test_compare_snapshots() {
+        let file = |path: &str, size: u64, hash: &str, category: &str| SnapshotFile {
+            path: PathBuf::from(path),
+            size,
+            last_modified: 12345,
+            hash: Some(hash.to_string()),
+            category: category.to_string(),
+        };
+        let snapshot = |created: u64, files: Vec<SnapshotFile>| Snapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            root: PathBuf::from("/data"),
+            created,
+            files,
+        };
+        
+        let a = snapshot(100, vec![
+            file("docs/report.pdf", 100, "h1", "Documents"),
+            file("docs/log.txt", 50, "h2", "Text"),
+            file("media/clip.mp4", 1000, "h3", "Videos"),
+            file("notes.txt", 10, "h4", "Text"),
+        ]);
+        let b = snapshot(200, vec![
+            file("docs/report.pdf", 100, "h1", "Documents"),
+            file("docs/log.txt", 80, "h5", "Text"),
+            file("archive/clip.mp4", 1000, "h3", "Videos"),
+            file("media/movie.mkv", 5000, "h6", "Videos"),
+        ]);
+        
+        let comparison = compare_snapshots(&a, &b, 1);
+        
+        assert_eq!(comparison.total_before, 1160);
+        assert_eq!(comparison.total_after, 6180);
+        assert_eq!(comparison.added.len(), 1);
+        assert_eq!(comparison.added[0].path, PathBuf::from("media/movie.mkv"));
+        assert_eq!(comparison.removed.len(), 1);
+        assert_eq!(comparison.removed[0].path, PathBuf::from("notes.txt"));
+        assert_eq!(comparison.grown.len(), 1);
+        assert_eq!(comparison.grown[0].path, PathBuf::from("docs/log.txt"));
+        assert_eq!(comparison.moved.len(), 1);
+        assert_eq!(comparison.moved[0].to, PathBuf::from("archive/clip.mp4"));
+        
+        // Only changed totals are kept, largest change first
+        assert_eq!(comparison.categories.len(), 2);
+        assert_eq!(comparison.categories[0].name, "Videos");
+        assert_eq!((comparison.categories[1].before, comparison.categories[1].after), (60, 80));
+        
+        let directories: Vec<&str> = comparison.directories.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(directories, vec!["media", "archive", "docs", "."]);
+        
+        assert_eq!(snapshot_directory(Path::new("a/b/c/file.txt"), 2), "a/b");
+        assert_eq!(snapshot_directory(Path::new("file.txt"), 2), ".");
+        
+        // Version 1 snapshots still load; unknown versions are refused
+        let dir = tempdir().unwrap();
+        let write = |name: &str, json: &str| {
+            let path = dir.path().join(name);
+            let mut encoder = flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default());
+            encoder.write_all(json.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let v1 = write("v1.json.gz", r#"{"schema_version":1,"root":"/data","created":1,"files":[{"path":"a","size":1,"modified":5,"category":"Text"}]}"#);
+        assert_eq!(load_snapshot(&v1).unwrap().files[0].last_modified, 5);
+        let future = write("v99.json.gz", r#"{"schema_version":99,"root":"/data","created":1,"files":[]}"#);
+        assert!(load_snapshot(&future).unwrap_err().to_string().contains("schema version 99"));
+        
+        // Two snapshots within the same second get different names
+        let (first, _) = create_unique_file(dir.path(), "100-data", "json.gz").unwrap();
+        let (second, _) = create_unique_file(dir.path(), "100-data", "json.gz").unwrap();
+        assert_eq!(first, dir.path().join("100-data.json.gz"));
+        assert_eq!(second, dir.path().join("100-data-2.json.gz"));
+    }
+
+    #[test]
+    fn test_evaluate_check_rules() {
+        let file = |path: &str, size: u64, category: FileCategory, age_days: u64, hash: &str| FileInfo {
+            last_modified: unix_now() - age_days * 86_400,
+            category,
+            hash: Some(hash.to_string()),
+            ..test_file(path, size)
+        };
+        let files = vec![
+            file("share/media/a.mp4", 3000, FileCategory::Video, 1, "h1"),
+            file("share/media/b.mp4", 3000, FileCategory::Video, 1, "h1"),
+            file("share/scratch/old.log", 10, FileCategory::Text, 1000, "h2"),
+            file("share/old.txt", 10, FileCategory::Text, 1000, "h3"),
+        ];
+        
+        assert_eq!(parse_size("2GB").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5 k").unwrap(), 1536);
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert!(parse_size("2XB").is_err());
+        assert_eq!(parse_age("2y").unwrap(), 730);
+        assert_eq!(parse_age("6mo").unwrap(), 180);
+        assert!(parse_age("2h").is_err());
+        
+        let rules: Vec<CheckRule> = serde_json::from_str(r#"[
+            { "category": "Videos", "max_total_size": "5KB", "max_file_size": "4KB" },
+            { "name": "scratch", "paths": ["scratch"], "max_age": "2y" },
+            { "max_duplicate_waste": "1KB" }
+        ]"#).unwrap();
+        let results = evaluate_check_rules(Path::new("share"), &files, &rules).unwrap();
+        
+        let outcomes: Vec<(&str, bool)> = results.iter().map(|r| (r.limit.as_str(), r.passed)).collect();
+        assert_eq!(outcomes, vec![
+            ("total size <= 5.00 KB", false),
+            ("file size <= 4.00 KB", true),
+            ("age <= 2y", false),
+            ("duplicate waste <= 1.00 KB", false),
+        ]);
+        
+        // Path scopes are relative to the checked directory
+        assert_eq!(results[2].rule, "scratch");
+        assert_eq!(results[2].files.len(), 1);
+        assert_eq!(results[2].files[0].path, PathBuf::from("share/scratch/old.log"));
+        
+        // The first copy by path is kept, the rest is waste
+        assert_eq!(results[3].files.len(), 1);
+        assert_eq!(results[3].files[0].path, PathBuf::from("share/media/b.mp4"));
+        
+        // `*` stays in one directory, `**` recurses, and `..` is resolved
+        let files = vec![
+            file("share/scratch/top.log", 10, FileCategory::Text, 1000, "h4"),
+            file("share/scratch/nested/deep.log", 10, FileCategory::Text, 1000, "h5"),
+        ];
+        let rules: Vec<CheckRule> = serde_json::from_str(r#"[
+            { "paths": ["scratch/*"], "max_files": 0 },
+            { "paths": ["scratch/**"], "max_files": 0 },
+            { "paths": ["../share/scratch"], "max_files": 0 }
+        ]"#).unwrap();
+        let results = evaluate_check_rules(Path::new("share"), &files, &rules).unwrap();
+        let counts: Vec<usize> = results.iter().map(|r| r.files.len()).collect();
+        assert_eq!(counts, vec![1, 2, 2]);
+        assert_eq!(results[0].files[0].path, PathBuf::from("share/scratch/top.log"));
+        
+        // Misspelled limits are rejected
+        assert!(serde_json::from_str::<Vec<CheckRule>>(r#"[{ "max_sise": "1GB" }]"#).is_err());
+        
+        // So are categories no scan can produce
+        let mut config = TidyConfig::default();
+        config.custom_categories.insert("Datasets".to_string(), vec!["parquet".to_string()]);
+        for category in ["Videos", "video", "Documents/Spreadsheets", "Other (.xyz)", "datasets"] {
+            assert!(known_check_category(category, &config), "{}", category);
+        }
+        assert!(!known_check_category("Vidoes", &config));
+        assert!(!known_check_category("Document/Spreadsheet", &config));
+    }
+
+    #[test]
+    fn test_ui_navigation_and_marking() {
+        use ratatui::crossterm::event::{KeyCode, KeyEvent};
+        
+        let file = |path: &str, size: u64, hash: &str| FileInfo {
+            category: FileCategory::Image,
+            hash: Some(hash.to_string()),
+            ..test_file(path, size)
+        };
+        let files = vec![
+            file("root/photos/2020/a.jpg", 300, "h1"),
+            file("root/photos/copy.jpg", 300, "h1"),
+            file("root/photos/b.jpg", 50, "h2"),
+            file("root/top.jpg", 10, "h3"),
+        ];
+        
+        let mut app = UiApp::new(Path::new("root"), files, true);
+        let names: Vec<&str> = app.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["photos/", "top.jpg"]);
+        assert_eq!(app.entries[0].size, 650);
+        
+        // Drill down and come back to the directory we left
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        let names: Vec<&str> = app.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["2020/", "copy.jpg", "b.jpg"]);
+        app.handle_key(KeyEvent::from(KeyCode::Down));
+        app.handle_key(KeyEvent::from(KeyCode::Left));
+        assert_eq!(app.location(), "root");
+        assert_eq!(app.table.selected(), Some(0));
+        
+        // Marking a directory marks every file beneath it
+        app.handle_key(KeyEvent::from(KeyCode::Char(' ')));
+        assert_eq!(app.marked.len(), 3);
+        app.handle_key(KeyEvent::from(KeyCode::Char('u')));
+        
+        // The duplicates view keeps the first copy of each group by path
+        app.handle_key(KeyEvent::from(KeyCode::Char('3')));
+        assert_eq!(app.entries.len(), 1);
+        assert_eq!(app.entries[0].size, 300);
+        app.handle_key(KeyEvent::from(KeyCode::Char('d')));
+        let marked: Vec<&Path> = app.marked_files().iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(marked, vec![Path::new("root/photos/copy.jpg")]);
+        
+        // Move asks for a target, then for confirmation
+        app.handle_key(KeyEvent::from(KeyCode::Char('m')));
+        for c in "/tmp/x".chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        assert_eq!(app.handle_key(KeyEvent::from(KeyCode::Enter)), UiStep::Continue);
+        assert_eq!(
+            app.handle_key(KeyEvent::from(KeyCode::Char('y'))),
+            UiStep::Execute(UiAction::Move(PathBuf::from("/tmp/x")))
+        );
+        
+        // Unknown organize methods are rejected
+        app.handle_key(KeyEvent::from(KeyCode::Char('o')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        app.mode = UiMode::Prompt(UiPrompt::OrganizeMethod(PathBuf::from("root")), "size".to_string());
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.mode, UiMode::Browse);
+        assert!(app.message.as_deref().unwrap().starts_with("Unknown method"));
+        
+        // Actions only run on marked files that are unchanged and still match a kept copy
+        let dir = tempdir().unwrap();
+        let keeper = create_test_file(dir.path(), "a.jpg", "same");
+        let copy = create_test_file(dir.path(), "b.jpg", "same");
+        let changed = create_test_file(dir.path(), "c.jpg", "same");
+        let other = create_test_file(dir.path(), "d.jpg", "other");
+        let options = ScanOptions { calculate_hashes: true, ..ScanOptions::default() };
+        let config = TidyConfig::default();
+        let files: Vec<FileInfo> = [&keeper, &copy, &changed, &other]
+            .iter()
+            .map(|p| get_file_info(p, &config, &options).unwrap())
+            .collect();
+        create_test_file(dir.path(), "c.jpg", "changed!");
+        
+        let mut app = UiApp::new(dir.path(), files, true);
+        for path in [&copy, &changed, &other] {
+            app.marked.insert(app.files.iter().position(|f| &f.path == path).unwrap());
+        }
+        let (ready, skipped) = app.verified_marked_files();
+        let ready: Vec<&Path> = ready.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(ready, vec![copy.as_path(), other.as_path()]);
+        assert_eq!(skipped, vec![(changed.clone(), "changed since scan".to_string())]);
+        
+        // With every copy marked there is no kept copy to verify against
+        app.marked.insert(app.files.iter().position(|f| f.path == keeper).unwrap());
+        let (ready, skipped) = app.verified_marked_files();
+        assert_eq!(ready.len(), 3);
+        assert_eq!(skipped.len(), 1);
+    }
+
+    #[test]
+    fn test_read_exif() {
+        let dir = tempdir().unwrap();
+        
+        // Big-endian TIFF: IFD0 with Make, Orientation and an Exif IFD pointer
+        let mut tiff: Vec<u8> = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend_from_slice(&[0, 3]);
+        tiff.extend_from_slice(&[0x01, 0x0F, 0, 2, 0, 0, 0, 4]);
+        tiff.extend_from_slice(b"Foo\0");
+        tiff.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        tiff.extend_from_slice(&[0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 50]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        // Exif IFD at offset 50 with DateTimeOriginal stored at offset 68
+        tiff.extend_from_slice(&[0, 1]);
+        tiff.extend_from_slice(&[0x90, 0x03, 0, 2, 0, 0, 0, 20, 0, 0, 0, 68]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        tiff.extend_from_slice(b"2021:06:15 12:30:00\0");
+        
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0, 2]);
+        
+        let path = dir.path().join("photo.jpg");
+        File::create(&path).unwrap().write_all(&jpeg).unwrap();
+        
+        let exif = read_exif(&path).unwrap();
+        assert_eq!(exif.camera_make.as_deref(), Some("Foo"));
+        assert_eq!(exif.camera_model, None);
+        assert_eq!(exif.orientation, Some(6));
+        
+        let taken = DateTime::<Utc>::from_timestamp(exif.date_taken.unwrap() as i64, 0).unwrap();
+        assert_eq!(taken.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(), "2021-06-15 12:30");
+        
+        // Images without EXIF fall back to mtime
+        let plain = dir.path().join("plain.png");
+        File::create(&plain).unwrap().write_all(b"\x89PNG\r\n\x1a\n").unwrap();
+        assert_eq!(read_exif(&plain), None);
+    }
+    
+    #[test]
+    fn test_audio_tags_and_templates() {
+        let dir = tempdir().unwrap();
+        
+        // ID3v2.3 tag with UTF-8 text frames
+        let frame = |id: &str, text: &str| {
+            let mut frame = id.as_bytes().to_vec();
+            frame.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+            frame.extend_from_slice(&[0, 0, 3]);
+            frame.extend_from_slice(text.as_bytes());
+            frame
+        };
+        let mut body = frame("TIT2", "Intro");
+        body.extend(frame("TPE1", "Some Artist"));
+        body.extend(frame("TALB", "Live: Tour"));
+        body.extend(frame("TRCK", "4/10"));
+        
+        let mut mp3 = b"ID3\x03\0\0".to_vec();
+        mp3.extend_from_slice(&[0, 0, (body.len() >> 7) as u8, (body.len() & 0x7F) as u8]);
+        mp3.extend(body);
+        
+        let path = dir.path().join("track.mp3");
+        File::create(&path).unwrap().write_all(&mp3).unwrap();
+        
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Intro"));
+        assert_eq!(tags.artist.as_deref(), Some("Some Artist"));
+        assert_eq!(tags.track, Some(4));
+        assert_eq!(tags.year, None);
+        
+        let mut file = FileInfo {
+            path: path.clone(),
+            last_modified: 0,
+            category: FileCategory::Audio,
+            audio: Some(tags),
+            ..test_file("", mp3.len() as u64)
+        };
+        
+        // Album artist falls back to artist, and path separators are replaced
+        assert_eq!(
+            render_template(MUSIC_TEMPLATE, &file).unwrap(),
+            PathBuf::from("Some Artist/Live_ Tour/04 - Intro.mp3")
+        );
+        assert_eq!(render_template("{year}/{title}", &file).unwrap_err(), vec!["year".to_string()]);
+        assert!(parse_template("{genre}").is_err());
+        
+        file.audio = None;
+        assert_eq!(render_template("{name}.{ext}", &file).unwrap(), PathBuf::from("track.mp3"));
+    }
+    
+    #[test]
+    fn test_read_video_info() {
+        let dir = tempdir().unwrap();
+        
+        let iso_box = |kind: &[u8], payload: &[u8]| {
+            let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+            data.extend_from_slice(kind);
+            data.extend_from_slice(payload);
+            data
+        };
+        
+        // mvhd v0: created 2020-09-13 (MP4 epoch), timescale 1000, 90 minutes
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend_from_slice(&(2_082_844_800u32 + 1_600_000_000).to_be_bytes());
+        mvhd.extend_from_slice(&[0; 4]);
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&5_400_000u32.to_be_bytes());
+        
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend_from_slice(b"vide");
+        let mut entry = vec![0u8; 24];
+        entry.extend_from_slice(&1920u16.to_be_bytes());
+        entry.extend_from_slice(&1080u16.to_be_bytes());
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(iso_box(b"avc1", &entry));
+        
+        let stbl = iso_box(b"stbl", &iso_box(b"stsd", &stsd));
+        let mut mdia = iso_box(b"hdlr", &hdlr);
+        mdia.extend(iso_box(b"minf", &stbl));
+        let mut moov = iso_box(b"mvhd", &mvhd);
+        moov.extend(iso_box(b"trak", &iso_box(b"mdia", &mdia)));
+        
+        // moov after mdat, as most cameras write it
+        let mut mp4 = iso_box(b"ftyp", b"isom\0\0\0\0");
+        mp4.extend(iso_box(b"mdat", &[0; 32]));
+        mp4.extend(iso_box(b"moov", &moov));
+        
+        let path = dir.path().join("clip.mp4");
+        File::create(&path).unwrap().write_all(&mp4).unwrap();
+        
+        let video = read_video_info(&path).unwrap();
+        assert_eq!(video.created, Some(1_600_000_000));
+        assert_eq!(video.duration_secs, Some(5400.0));
+        assert_eq!((video.width, video.height), (Some(1920), Some(1080)));
+        assert_eq!(video.codec.as_deref(), Some("H.264"));
+        
+        // A v1 mvhd with a garbage 64-bit creation time is ignored
+        let mut mvhd = vec![1, 0, 0, 0];
+        mvhd.extend_from_slice(&u64::MAX.to_be_bytes());
+        mvhd.extend_from_slice(&[0; 8]);
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&5_400_000u64.to_be_bytes());
+        let mut mp4 = iso_box(b"ftyp", b"isom\0\0\0\0");
+        mp4.extend(iso_box(b"moov", &iso_box(b"mvhd", &mvhd)));
+        let garbage = dir.path().join("garbage.mp4");
+        File::create(&garbage).unwrap().write_all(&mp4).unwrap();
+        
+        let video = read_video_info(&garbage).unwrap();
+        assert_eq!(video.created, None);
+        assert_eq!(video.duration_secs, Some(5400.0));
+        
+        // Matroska with an oversized TimecodeScale keeps the default scale and the track info
+        let ebml = |id: &[u8], payload: &[u8]| {
+            let mut data = id.to_vec();
+            data.push(0x80 | payload.len() as u8);
+            data.extend_from_slice(payload);
+            data
+        };
+        let mut info = ebml(&[0x2A, 0xD7, 0xB1], &[0xFF; 9]);
+        info.extend(ebml(&[0x44, 0x89], &90_000.0f64.to_be_bytes()));
+        let mut video_settings = ebml(&[0xB0], &1280u16.to_be_bytes());
+        video_settings.extend(ebml(&[0xBA], &720u16.to_be_bytes()));
+        let mut track = ebml(&[0x83], &[1]);
+        track.extend(ebml(&[0x86], b"V_VP9"));
+        track.extend(ebml(&[0xE0], &video_settings));
+        let mut segment = ebml(&[0x15, 0x49, 0xA9, 0x66], &info);
+        segment.extend(ebml(&[0x16, 0x54, 0xAE, 0x6B], &ebml(&[0xAE], &track)));
+        let mut mkv = ebml(&[0x1A, 0x45, 0xDF, 0xA3], &[]);
+        mkv.extend(ebml(&[0x18, 0x53, 0x80, 0x67], &segment));
+        let wide_scale = dir.path().join("wide-scale.mkv");
+        File::create(&wide_scale).unwrap().write_all(&mkv).unwrap();
+        
+        let video = read_video_info(&wide_scale).unwrap();
+        assert_eq!(video.duration_secs, Some(90.0));
+        assert_eq!((video.width, video.height), (Some(1280), Some(720)));
+        assert!(video.codec.is_some());
+        
+        // Dates chrono can't represent fall back to mtime instead of panicking
+        let (folder, source) = dated_folder(Some(u64::MAX), "container", 1_600_000_000);
+        assert_eq!((folder, source), (month_folder(1_600_000_000).unwrap(), "mtime"));
+        assert_eq!(dated_folder(None, "EXIF", u64::MAX), ("unknown_date".to_string(), "mtime"));
+        
+        // --by date only reads video metadata
+        assert_eq!(organize_metadata("date", false), MetadataKinds { video: true, ..MetadataKinds::default() });
+        assert_eq!(organize_metadata("type", true), MetadataKinds::ALL);
+        
+        assert_eq!(parse_duration("90").unwrap(), 90.0);
+        assert_eq!(parse_duration("1.5h").unwrap(), 5400.0);
+        assert!(parse_duration("long").is_err());
+        assert!(parse_duration("-5m").is_err());
+        assert!(parse_duration("inf").is_err());
+    }
+    
+    #[test]
+    fn test_read_document_info() {
+        let dir = tempdir().unwrap();
+        
+        let docx_path = dir.path().join("report.docx");
+        let mut docx = zip::ZipWriter::new(File::create(&docx_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        docx.start_file("[Content_Types].xml", options).unwrap();
+        docx.start_file("docProps/core.xml", options).unwrap();
+        docx.write_all(
+            b"<cp:coreProperties><dc:title>Q1 &amp; Q2</dc:title><dc:creator>Jane Doe</dc:creator>\
+              <dcterms:created xsi:type=\"dcterms:W3CDTF\">2019-03-04T05:06:07Z</dcterms:created></cp:coreProperties>",
+        )
+        .unwrap();
+        docx.start_file("docProps/app.xml", options).unwrap();
+        docx.write_all(b"<Properties><Pages>12</Pages></Properties>").unwrap();
+        docx.finish().unwrap();
+        
+        let info = read_document_info(&docx_path).unwrap();
+        assert_eq!(info.title.as_deref(), Some("Q1 & Q2"));
+        assert_eq!(info.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(info.created, Some(1_551_675_967));
+        assert_eq!(info.pages, Some(12));
+        
+        let pdf_path = dir.path().join("paper.pdf");
+        File::create(&pdf_path)
+            .unwrap()
+            .write_all(
+                b"%PDF-1.4\n1 0 obj << /Type /Catalog /Pages 2 0 R /Lang (en) >> endobj\n\
+                  2 0 obj << /Type /Pages /Kids [] /Count 7 >> endobj\n\
+                  4 0 obj << /Title <FEFF00480069> /Author (A \\(B\\) C) /CreationDate (D:20210315103000Z) >> endobj\n\
+                  trailer << /Root 1 0 R /Info 4 0 R >>\n%%EOF",
+            )
+            .unwrap();
+        
+        let info = read_document_info(&pdf_path).unwrap();
+        assert_eq!(info.title.as_deref(), Some("Hi"));
+        assert_eq!(info.author.as_deref(), Some("A (B) C"));
+        assert_eq!(info.created, Some(1_615_804_200));
+        assert_eq!(info.pages, Some(7));
+        assert_eq!(info.language.as_deref(), Some("en"));
+    }
+    
+    #[test]
+    fn test_build_directory_tree() {
+        let dir = tempdir().unwrap();
+        create_test_file(&dir.path(), "top.txt", "12345");
+        fs::create_dir_all(dir.path().join("photos/2020")).unwrap();
+        create_test_file(&dir.path().join("photos"), "a.jpg", "1234567890");
+        create_test_file(&dir.path().join("photos/2020"), "b.jpg", "123");
+        
+        let config = TidyConfig::default();
+        let options = ScanOptions { recursive: true, ..ScanOptions::default() };
+        let files = scan_directory(&dir.path(), &config, &options).unwrap();
+        
+        let mut tree = build_directory_tree(dir.path(), &files);
+        assert_eq!((tree.size, tree.files), (18, 3));
+        
+        let photos = &tree.children["photos"];
+        assert_eq!((photos.size, photos.files), (13, 2));
+        assert_eq!(photos.children["2020"].size, 3);
+        
+        // Pruned directories still count towards their parent
+        prune_directory_tree(&mut tree, 5);
+        assert!(tree.children["photos"].children.is_empty());
+        assert_eq!(tree.children["photos"].size, 13);
+        
+        // Freshly written files all land in the newest age bucket
+        let buckets = build_age_buckets(&files);
+        assert_eq!(buckets.len(), AGE_BUCKETS.len());
+        assert_eq!((buckets[0].files, buckets[0].size), (3, 18));
+    }
+    
     #[test]
     fn test_scan_directory() {
         let dir = tempdir().unwrap();
@@ -180,7 +1326,8 @@ mod tests {
         // Create some test files
         create_test_file(&dir.path(), "doc.pdf", "pdf content");
         create_test_file(&dir.path(), "image.jpg", "jpg content");
-        create_test_file(&dir.path(), "unknown.xyz", "unknown content");
+        // Binary content, so text detection leaves it in Other
+        create_test_file(&dir.path(), "unknown.xyz", "\0unknown content");
         
         // Create a subdirectory with a file
         let subdir = dir.path().join("subdir");
@@ -189,13 +1336,13 @@ mod tests {
         
         // Scan without recursion
         let config = TidyConfig::default();
-        let files = scan_directory(&dir.path(), &config, false, false).unwrap();
+        let files = scan_directory(&dir.path(), &config, &ScanOptions { recursive: false, detect_projects: true, ..ScanOptions::default() }).unwrap();
         
         // Should find 3 files (not including the file in the subdirectory)
         assert_eq!(files.len(), 3);
         
         // Scan with recursion
-        let files_recursive = scan_directory(&dir.path(), &config, false, true).unwrap();
+        let files_recursive = scan_directory(&dir.path(), &config, &ScanOptions { recursive: true, detect_projects: true, ..ScanOptions::default() }).unwrap();
         
         // Should find 4 files (including the file in the subdirectory)
         assert_eq!(files_recursive.len(), 4);
@@ -216,6 +1363,41 @@ mod tests {
         assert!(categories.contains(&"Other(xyz)".to_string()));
     }
 
+    #[test]
+    fn test_scan_directory_projects() {
+        let dir = tempdir().unwrap();
+        
+        create_test_file(&dir.path(), "notes.md", "notes");
+        
+        // A Rust project with sources and a solution folder found via glob marker
+        let project = dir.path().join("tool");
+        fs::create_dir_all(project.join("src")).unwrap();
+        create_test_file(&project, "Cargo.toml", "[package]");
+        create_test_file(&project.join("src"), "main.rs", "fn main() {}");
+        
+        let solution = dir.path().join("app");
+        fs::create_dir(&solution).unwrap();
+        create_test_file(&solution, "App.sln", "sln");
+        
+        let config = TidyConfig::default();
+        let files = scan_directory(&dir.path(), &config, &ScanOptions { recursive: true, detect_projects: true, ..ScanOptions::default() }).unwrap();
+        
+        // notes.md plus the two projects as single items
+        assert_eq!(files.len(), 3);
+        
+        let tool = files.iter().find(|f| f.path == project).unwrap();
+        assert!(matches!(tool.category, FileCategory::Project));
+        assert_eq!(tool.subcategory.as_deref(), Some("Rust"));
+        assert_eq!(tool.size, ("[package]".len() + "fn main() {}".len()) as u64);
+        
+        let app = files.iter().find(|f| f.path == solution).unwrap();
+        assert_eq!(app.subcategory.as_deref(), Some(".NET"));
+        
+        // Detection can be turned off to look inside projects
+        let files = scan_directory(&dir.path(), &config, &ScanOptions { recursive: true, detect_projects: false, ..ScanOptions::default() }).unwrap();
+        assert_eq!(files.len(), 4);
+    }
+
     #[test]
     fn test_config_save_load() {
         let dir = tempdir().unwrap();